@@ -1,6 +1,14 @@
 extern crate cc;
 
+use std::env;
+
 fn main() {
+    // The CUDA code needs nvcc and the CUDA toolkit, so only build it when the `cuda` feature
+    // is enabled
+    if env::var("CARGO_FEATURE_CUDA").is_err() {
+        return;
+    }
+
     cc::Build::new()
         .cuda(true)
         //.flag("-cudart=shared")
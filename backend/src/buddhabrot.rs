@@ -0,0 +1,543 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use rand::distributions::StandardNormal;
+use rand::{thread_rng, Rng};
+
+use crate::compute::Result;
+use crate::formula::Formula;
+use crate::math::{is_integer, Complex};
+use crate::render::{main_bulb_period, Bailout, FractalKind, Render, RenderError};
+use crate::threadpool::ThreadPool;
+
+// Orbits are started from c anywhere in the square with this half-width, which holds the whole
+// Mandelbrot set
+const SAMPLE_RADIUS: f64 = 2.0;
+
+// Views with a smaller radius than this are sampled with Metropolis-Hastings, since hardly any
+// orbits started uniformly would pass through them
+const METROPOLIS_RADIUS: f64 = 1.0;
+
+// Chance of a Metropolis-Hastings mutation jumping to anywhere in the sampled square, rather than
+// moving c a little, which keeps the chain from getting stuck in one place
+const JUMP_CHANCE: f64 = 0.2;
+
+// Size of the small moves, as a fraction of the view's radius
+const MUTATION_SIZE: f64 = 0.01;
+
+// Each worker collects this many hits before adding them to the shared density
+const BATCH_SIZE: usize = 1 << 16;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+// Range of iterations, inclusive, that one color channel of a Nebulabrot takes its hits from
+pub struct Window {
+    pub min: u32,
+    pub max: u32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+// Settings for the Buddhabrot and Anti-Buddhabrot
+pub struct Buddhabrot {
+    // Number of orbits to sample
+    pub samples: u64,
+    // Iteration windows for the red, green and blue channels
+    pub windows: [Window; 3],
+}
+
+#[derive(Clone, Debug)]
+// The Buddhabrot's accumulation buffer, kept in the render so that more samples can be added later
+pub struct Density {
+    // Number of hits on each pixel in each of the red, green and blue channels, which are weighted
+    // when sampling with Metropolis-Hastings
+    pub hits: Vec<[f64; 3]>,
+    // Number of orbits sampled so far
+    pub samples: u64,
+}
+
+impl Window {
+    fn contains(self, i: u32) -> bool {
+        self.min <= i && i <= self.max
+    }
+}
+
+impl Default for Buddhabrot {
+    // The usual Nebulabrot, with the longest orbits in red and the shortest in blue.  Orbits that
+    // escape straight away land all over the view, so they're left out.
+    fn default() -> Buddhabrot {
+        Buddhabrot {
+            samples: 10_000_000,
+            windows: [
+                Window { min: 100, max: 500 },
+                Window { min: 20, max: 100 },
+                Window { min: 5, max: 20 },
+            ],
+        }
+    }
+}
+
+// Everything needed to trace an orbit and work out which pixels and channels it hits
+struct Tracer {
+    iterations: u32,
+    windows: [Window; 3],
+    // Whether to trace the orbits that don't escape, for the Anti-Buddhabrot
+    anti: bool,
+    power: f64,
+    formula: Option<Formula>,
+    // Bailout and escape radius that orbits have to get past to count as escaping
+    escape: (Bailout, f64),
+    size: (u32, u32),
+    // Position of the top left pixel, and the distance between pixels in each direction
+    origin: Complex,
+    spacing: Complex,
+}
+
+impl Tracer {
+    fn step(&self, z: Complex, c: Complex) -> Complex {
+        match &self.formula {
+            Some(formula) => formula.evaluate(z, c),
+            None if is_integer(self.power) => z.powi(self.power as i32) + c,
+            None => z.powf(self.power) + c,
+        }
+    }
+
+    // Index of the pixel that z lands on, if it's in the view
+    fn pixel(&self, z: Complex) -> Option<usize> {
+        let x = ((z.0 - self.origin.0) / self.spacing.0 + 0.5).floor();
+        let y = ((z.1 - self.origin.1) / self.spacing.1 + 0.5).floor();
+
+        if x >= 0.0 && x < self.size.0 as f64 && y >= 0.0 && y < self.size.1 as f64 {
+            Some(x as usize + y as usize * self.size.0 as usize)
+        } else {
+            None
+        }
+    }
+
+    // Iterate the orbit of c, and fill `hits` with the pixels it lands on in the view, along with
+    // which channels count each of them.  The Buddhabrot counts every point of an orbit that
+    // escapes in a channel whose window holds its number of iterations, while the Anti-Buddhabrot
+    // counts the points of an orbit that doesn't escape in the channels whose windows hold their
+    // own iteration.  Returns the total number of hits.
+    fn trace(
+        &self,
+        c: Complex,
+        orbit: &mut Vec<Complex>,
+        hits: &mut Vec<(usize, [f64; 3])>,
+    ) -> f64 {
+        orbit.clear();
+        hits.clear();
+
+        // Orbits that start in the main cardioid or period 2 bulb stay within |z| <= 2, so with the
+        // usual bailout and a large enough radius they never escape, and the Buddhabrot can skip
+        // them without iterating
+        if !self.anti
+            && self.power == 2.0
+            && self.formula.is_none()
+            && self.escape.0 == Bailout::Norm
+            && self.escape.1 >= 2.0
+            && main_bulb_period(c).is_some()
+        {
+            return 0.0;
+        }
+
+        let mut z = Complex(0.0, 0.0);
+        let mut escaped = false;
+        while orbit.len() < self.iterations as usize && !escaped {
+            z = self.step(z, c);
+            orbit.push(z);
+            escaped = self.escape.0.escaped(&z, self.escape.1);
+        }
+
+        if escaped == self.anti {
+            return 0.0;
+        }
+
+        let mut total = 0.0;
+        for (j, z) in orbit.iter().enumerate() {
+            let i = if self.anti {
+                j as u32 + 1
+            } else {
+                orbit.len() as u32
+            };
+            let mut channels = [0.0; 3];
+            for (channel, window) in channels.iter_mut().zip(&self.windows) {
+                if window.contains(i) {
+                    *channel = 1.0;
+                }
+            }
+
+            let count: f64 = channels.iter().sum();
+            if count > 0.0 {
+                if let Some(idx) = self.pixel(*z) {
+                    hits.push((idx, channels));
+                    total += count;
+                }
+            }
+        }
+
+        total
+    }
+}
+
+pub fn compute(
+    mut render: Render,
+    progress: Arc<Mutex<Option<f64>>>,
+    cancel: Arc<AtomicBool>,
+) -> Result {
+    let params = &render.params;
+    let (width, height) = params.image_size;
+    let radius = params.radius.to_f64();
+
+    // The mapping gives each pixel's position, so the hits are put into pixels by reversing it
+    let mapping = Complex::<f64>::get_mapping(
+        params.image_size,
+        (&params.center, &params.radius),
+        params.fractal.flipped(),
+    );
+    let origin = mapping(0, 0);
+    let spacing = Complex(mapping(1, 0).0 - origin.0, mapping(0, 1).1 - origin.1);
+
+    let tracer = Arc::new(Tracer {
+        iterations: params.max_iter,
+        windows: params.buddhabrot.windows,
+        anti: params.fractal == FractalKind::AntiBuddhabrot,
+        power: params.power,
+        formula: params.formula.clone(),
+        escape: (params.bailout, params.escape_radius),
+        size: (width, height),
+        origin,
+        spacing,
+    });
+
+    // Pick up from the samples taken last time, if there were any
+    let target = params.buddhabrot.samples;
+    let density = render.density.take().unwrap_or_else(|| Density {
+        hits: vec![[0.0; 3]; (width * height) as usize],
+        samples: 0,
+    });
+    let previous = density.samples;
+    let remaining = target.saturating_sub(previous);
+    let hits = Arc::new(Mutex::new(density.hits));
+
+    let metropolis = if radius < METROPOLIS_RADIUS {
+        Some(radius * MUTATION_SIZE)
+    } else {
+        None
+    };
+
+    // Progress counter, in number of samples
+    let done = Arc::new(AtomicU64::new(0));
+
+    // Each worker sends on this channel when it has taken its share of the samples
+    let (sender, receiver) = mpsc::channel();
+
+    // Use one worker per core, each with its own share of the samples
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let pool = ThreadPool::new(threads, sender);
+
+    for thread in 0..threads as u64 {
+        let samples = remaining / threads as u64 + u64::from(thread < remaining % threads as u64);
+        let tracer = Arc::clone(&tracer);
+        let hits = Arc::clone(&hits);
+        let done = Arc::clone(&done);
+        let cancel = Arc::clone(&cancel);
+
+        pool.execute(move |sender| {
+            let mut worker = Worker {
+                tracer: &tracer,
+                hits: &hits,
+                done: &done,
+                cancel: &cancel,
+                batch: Vec::with_capacity(BATCH_SIZE),
+            };
+
+            let mut rng = thread_rng();
+            match metropolis {
+                Some(size) => worker.metropolis(samples, size, &mut rng),
+                None => worker.uniform(samples, &mut rng),
+            }
+
+            worker.flush();
+            sender.send(()).unwrap();
+        });
+    }
+
+    // Wait for every worker to finish, updating the progress in the meantime
+    let mut finished = 0;
+    while finished < threads {
+        if receiver.recv_timeout(Duration::from_millis(10)).is_ok() {
+            finished += 1;
+        }
+
+        let p = (previous + done.load(Ordering::Relaxed)) as f64 / target.max(1) as f64 * 100.0;
+        *progress.lock().unwrap() = Some(p.min(100.0));
+    }
+
+    // Shut down the workers, which releases their references to the hits
+    drop(pool);
+
+    if cancel.load(Ordering::Relaxed) {
+        return Err(RenderError("Render cancelled.".into()));
+    }
+
+    let hits = match Arc::try_unwrap(hits) {
+        Ok(hits) => hits.into_inner().unwrap(),
+        Err(_) => return Err(RenderError("Worker threads did not finish.".into())),
+    };
+
+    Ok(Render {
+        iterations: render.params.max_iter,
+        density: Some(Density {
+            hits,
+            samples: previous + done.load(Ordering::Relaxed),
+        }),
+        ..render
+    })
+}
+
+// One thread's share of the sampling, which adds its hits to the shared density in batches
+struct Worker<'a> {
+    tracer: &'a Tracer,
+    hits: &'a Mutex<Vec<[f64; 3]>>,
+    done: &'a AtomicU64,
+    cancel: &'a AtomicBool,
+    batch: Vec<(usize, [f64; 3])>,
+}
+
+impl<'a> Worker<'a> {
+    // Start every orbit from a c picked uniformly from the sampled square
+    fn uniform<R: Rng>(&mut self, samples: u64, rng: &mut R) {
+        let (mut orbit, mut hits) = (Vec::new(), Vec::new());
+
+        for _ in 0..samples {
+            if self.cancel.load(Ordering::Relaxed) {
+                return;
+            }
+
+            self.tracer.trace(random_point(rng), &mut orbit, &mut hits);
+            self.add(&hits, 1.0);
+            self.done.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Pick each orbit's c by mutating the last one, and keep it with a chance that depends on how
+    // many hits it has in the view compared to the last, so that most of the samples are spent on
+    // orbits that pass through the view.  Each mutation is as likely as its reverse, so c ends up
+    // being picked in proportion to its number of hits, and each orbit is weighted by one over that
+    // to get back to the density that uniform sampling would give.
+    fn metropolis<R: Rng>(&mut self, samples: u64, size: f64, rng: &mut R) {
+        let (mut orbit, mut hits, mut current_hits) = (Vec::new(), Vec::new(), Vec::new());
+
+        // The current c and its number of hits, once an orbit that hits the view has been found,
+        // and how many samples it's been kept for
+        let mut current: Option<(Complex, f64)> = None;
+        let mut kept = 0;
+
+        for _ in 0..samples {
+            if self.cancel.load(Ordering::Relaxed) {
+                return;
+            }
+
+            let c = match current {
+                Some((c, _)) if rng.gen::<f64>() >= JUMP_CHANCE => {
+                    let x: f64 = rng.sample(StandardNormal);
+                    let y: f64 = rng.sample(StandardNormal);
+                    c + Complex(x * size, y * size)
+                }
+                _ => random_point(rng),
+            };
+
+            // c has no chance of being picked outside of the sampled square
+            let total = if c.0.abs() <= SAMPLE_RADIUS && c.1.abs() <= SAMPLE_RADIUS {
+                self.tracer.trace(c, &mut orbit, &mut hits)
+            } else {
+                0.0
+            };
+            self.done.fetch_add(1, Ordering::Relaxed);
+
+            let accept = match current {
+                _ if total == 0.0 => false,
+                Some((_, current_total)) => rng.gen::<f64>() < total / current_total,
+                None => true,
+            };
+
+            if accept {
+                // Add the orbit that's being left behind, for every sample it was kept
+                if let Some((_, current_total)) = current {
+                    self.add(&current_hits, kept as f64 / current_total);
+                }
+
+                current = Some((c, total));
+                kept = 1;
+                std::mem::swap(&mut hits, &mut current_hits);
+            } else if current.is_some() {
+                kept += 1;
+            }
+        }
+
+        if let Some((_, current_total)) = current {
+            self.add(&current_hits, kept as f64 / current_total);
+        }
+    }
+
+    // Add an orbit's hits, each with the given weight
+    fn add(&mut self, hits: &[(usize, [f64; 3])], weight: f64) {
+        for (idx, channels) in hits {
+            let mut weighted = *channels;
+            for channel in weighted.iter_mut() {
+                *channel *= weight;
+            }
+            self.batch.push((*idx, weighted));
+        }
+
+        if self.batch.len() >= BATCH_SIZE {
+            self.flush();
+        }
+    }
+
+    // Add the batch to the shared density
+    fn flush(&mut self) {
+        let mut density = self.hits.lock().unwrap();
+        for (idx, channels) in self.batch.drain(..) {
+            for (total, hits) in density[idx].iter_mut().zip(&channels) {
+                *total += hits;
+            }
+        }
+    }
+}
+
+fn random_point<R: Rng>(rng: &mut R) -> Complex {
+    Complex(
+        rng.gen_range(-SAMPLE_RADIUS, SAMPLE_RADIUS),
+        rng.gen_range(-SAMPLE_RADIUS, SAMPLE_RADIUS),
+    )
+}
+
+// Allow for parsing windows from user input, as the minimum and maximum iterations separated by a
+// comma
+impl FromStr for Window {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parsed = match s.split_once(',') {
+            Some((min, max)) => min
+                .trim()
+                .parse()
+                .and_then(|min| Ok((min, max.trim().parse()?))),
+            None => return Err(format!("Invalid window: {}.", s)),
+        };
+
+        match parsed {
+            Ok((min, max)) if min <= max => Ok(Window { min, max }),
+            _ => Err(format!("Invalid window: {}.", s)),
+        }
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.min, self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn windows_round_trip() {
+        for s in &["100,500", "0,0", "5,20"] {
+            let window: Window = s.parse().unwrap();
+            assert_eq!(window.to_string(), *s);
+        }
+        assert_eq!(" 20 , 100 ".parse(), Ok(Window { min: 20, max: 100 }));
+
+        for s in &["", "5", "20,5", "-1,5", "1,2,3", "a,b"] {
+            assert!(s.parse::<Window>().is_err(), "{}", s);
+        }
+    }
+
+    // Tracer for the usual Nebulabrot over a 32 by 32 view of the whole set
+    fn tracer() -> Tracer {
+        Tracer {
+            iterations: 500,
+            windows: Buddhabrot::default().windows,
+            anti: false,
+            power: 2.0,
+            formula: None,
+            escape: (Bailout::Norm, 2.0),
+            size: (32, 32),
+            origin: Complex(-2.0, -2.0),
+            spacing: Complex(0.125, 0.125),
+        }
+    }
+
+    // Sample with a seeded RNG, giving the density and how many samples were taken
+    fn sample(seed: u64, metropolis: Option<f64>) -> (Vec<[f64; 3]>, u64) {
+        let tracer = tracer();
+        let hits = Mutex::new(vec![[0.0; 3]; 32 * 32]);
+        let (done, cancel) = (AtomicU64::new(0), AtomicBool::new(false));
+        let mut worker = Worker {
+            tracer: &tracer,
+            hits: &hits,
+            done: &done,
+            cancel: &cancel,
+            batch: Vec::new(),
+        };
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        match metropolis {
+            Some(size) => worker.metropolis(2000, size, &mut rng),
+            None => worker.uniform(2000, &mut rng),
+        }
+        worker.flush();
+
+        (hits.into_inner().unwrap(), done.load(Ordering::Relaxed))
+    }
+
+    #[test]
+    fn seeded_sampling_is_deterministic() {
+        let (density, samples) = sample(1, None);
+        assert_eq!(samples, 2000);
+        assert_eq!(sample(1, None).0, density);
+        assert_ne!(sample(2, None).0, density);
+
+        // Uniform sampling adds up the hits of each orbit traced from the same points
+        let tracer = tracer();
+        let mut rng = StdRng::seed_from_u64(1);
+        let (mut orbit, mut hits) = (Vec::new(), Vec::new());
+        let mut expected = vec![[0.0; 3]; 32 * 32];
+        for _ in 0..2000 {
+            tracer.trace(random_point(&mut rng), &mut orbit, &mut hits);
+            for (idx, channels) in &hits {
+                for (total, hit) in expected[*idx].iter_mut().zip(channels) {
+                    *total += hit;
+                }
+            }
+        }
+        assert_eq!(density, expected);
+        assert!(density
+            .iter()
+            .any(|channels| channels.iter().all(|&hits| hits > 0.0)));
+
+        // The Buddhabrot is symmetric about the real axis, which runs through the middle of row 16
+        let half = |rows: std::ops::Range<usize>| -> f64 {
+            rows.flat_map(|y| &density[y * 32..(y + 1) * 32])
+                .map(|channels| channels.iter().sum::<f64>())
+                .sum()
+        };
+        let (top, bottom) = (half(1..16), half(17..32));
+        assert!((top - bottom).abs() < (top + bottom) * 0.2);
+
+        let (metropolis, samples) = sample(1, Some(0.01));
+        assert_eq!(samples, 2000);
+        assert_eq!(sample(1, Some(0.01)).0, metropolis);
+        assert!(metropolis.iter().flatten().sum::<f64>() > 0.0);
+    }
+}
@@ -2,10 +2,16 @@ use std::fs;
 use std::io::{self, prelude::*};
 use std::path::Path;
 
+use crate::buddhabrot::Window;
 use crate::colors::*;
+use crate::compute::Backend;
+use crate::formula::Formula;
 use crate::image::*;
+use crate::lyapunov::Sequence;
 use crate::math::*;
+use crate::newton::Polynomial;
 use crate::render::*;
+use crate::trap::Trap;
 use crate::util::{self, *};
 
 #[derive(Copy, Clone)]
@@ -18,6 +24,25 @@ enum Field {
     Radius,
     Supersampling,
     ColorFunc,
+    Backend,
+    Fractal,
+    JuliaX,
+    JuliaY,
+    Power,
+    Formula,
+    Roots,
+    Samples,
+    // Buddhabrot iteration window for the red, green or blue channel
+    Window(usize),
+    Sequence,
+    Subdivision,
+    Progressive,
+    EscapeRadius,
+    Bailout,
+    InteriorStats,
+    Trap,
+    Averages,
+    StripeDensity,
 }
 
 #[derive(Clone)]
@@ -25,6 +50,7 @@ struct Data {
     render: Render,
     params: Parameters,
     colorfunc: ColorFunction,
+    backend: Backend,
 }
 
 #[derive(Clone)]
@@ -38,7 +64,7 @@ enum State {
     Dead,
 }
 
-const FIELDS: [(&str, Field); 8] = [
+const FIELDS: [(&str, Field); 28] = [
     ("iterations", Field::Iterations),
     ("width", Field::Width),
     ("height", Field::Height),
@@ -47,6 +73,26 @@ const FIELDS: [(&str, Field); 8] = [
     ("radius", Field::Radius),
     ("supersampling", Field::Supersampling),
     ("colorfunc", Field::ColorFunc),
+    ("backend", Field::Backend),
+    ("fractal", Field::Fractal),
+    ("julia:x", Field::JuliaX),
+    ("julia:y", Field::JuliaY),
+    ("power", Field::Power),
+    ("formula", Field::Formula),
+    ("roots", Field::Roots),
+    ("buddhabrot:samples", Field::Samples),
+    ("buddhabrot:red", Field::Window(0)),
+    ("buddhabrot:green", Field::Window(1)),
+    ("buddhabrot:blue", Field::Window(2)),
+    ("sequence", Field::Sequence),
+    ("subdivision", Field::Subdivision),
+    ("progressive", Field::Progressive),
+    ("escape_radius", Field::EscapeRadius),
+    ("bailout", Field::Bailout),
+    ("interior_stats", Field::InteriorStats),
+    ("trap", Field::Trap),
+    ("averages", Field::Averages),
+    ("stripe_density", Field::StripeDensity),
 ];
 
 impl State {
@@ -69,7 +115,7 @@ impl State {
                 reader.read_line(&mut input).unwrap();
 
                 // If the input is empty, exit
-                if input == "" {
+                if input.is_empty() {
                     println!("exit");
                     return State::Dead;
                 }
@@ -78,18 +124,18 @@ impl State {
                 input = input.trim().to_string();
 
                 // Parse the input
-                if input == "" {
+                if input.is_empty() {
                     // Input was all whitespace, user probably just hit enter, show help hint
                     println!("Enter `help` for possible commands");
                     State::Prompt(data)
                 } else if input == "quit" || input == "exit" {
                     // Enter a dead state, causing the program to stop
                     State::Dead
-                } else if input.starts_with("set ") {
+                } else if let Some(input) = input.strip_prefix("set ") {
                     // Requesting to set a field
 
                     // Get the part of the input after the "set", and trim whitespace
-                    let input = (&input[4..]).to_string().trim().to_string();
+                    let input = input.trim().to_string();
 
                     // The first word is the name of the field
                     let first_word = input.split_whitespace().next().unwrap().to_string();
@@ -101,15 +147,15 @@ impl State {
                             let n = string.len() + 1;
 
                             // Ensure the string is long enough, to prevent illegal index panics
-                            if input.len() <= n - 1 {
+                            if input.len() < n {
                                 println!("Must specify a value.");
                                 return State::Prompt(data);
                             }
 
                             return State::Set(
                                 data,
-                                field.clone(),
-                                (&input[n..]).to_string().trim().to_string(),
+                                *field,
+                                input[n..].trim().to_string(),
                             );
                         }
                     }
@@ -119,16 +165,16 @@ impl State {
                         input.split_whitespace().next().unwrap()
                     );
                     State::Prompt(data)
-                } else if input.starts_with("get ") {
+                } else if let Some(input) = input.strip_prefix("get ") {
                     // Requesting to read the value of a field
 
                     // Get the part of the input after the "get", and trim whitesapce
-                    let input = (&input[4..]).to_string().trim().to_string();
+                    let input = input.trim().to_string();
 
                     // Find the Field enum that matches
                     for (string, field) in FIELDS.iter() {
                         if input == *string {
-                            return State::Get(data, field.clone());
+                            return State::Get(data, *field);
                         }
                     }
 
@@ -142,13 +188,14 @@ impl State {
                 } else if input == "render" {
                     State::Render(data)
                 } else if input.starts_with("export ") || input.starts_with("saveconfig ") {
-                    let path = Path::new(if input.starts_with("export ") {
-                        &input[7..]
-                    } else {
-                        &input[11..]
-                    });
+                    let path = Path::new(
+                        input
+                            .strip_prefix("export ")
+                            .or_else(|| input.strip_prefix("saveconfig "))
+                            .unwrap(),
+                    );
 
-                    let valid = util::can_make_file_here(&path);
+                    let valid = util::can_make_file_here(path);
 
                     if !valid {
                         State::Prompt(data)
@@ -193,6 +240,28 @@ impl State {
                     Field::Radius => println!("{}", data.params.radius),
                     Field::Supersampling => println!("{}", data.params.supersampling),
                     Field::ColorFunc => println!("{}", data.colorfunc.info()),
+                    Field::Backend => println!("{}", data.backend),
+                    Field::Fractal => println!("{}", data.params.fractal),
+                    Field::JuliaX => println!("{}", data.params.julia.0),
+                    Field::JuliaY => println!("{}", data.params.julia.1),
+                    Field::Power => println!("{}", data.params.power),
+                    Field::Formula => {
+                        println!("{}", Formula::describe(data.params.formula.as_ref()))
+                    }
+                    Field::Roots => println!("{}", data.params.polynomial),
+                    Field::Samples => println!("{}", data.params.buddhabrot.samples),
+                    Field::Window(channel) => {
+                        println!("{}", data.params.buddhabrot.windows[channel])
+                    }
+                    Field::Sequence => println!("{}", data.params.sequence),
+                    Field::Subdivision => println!("{}", data.params.subdivision),
+                    Field::Progressive => println!("{}", data.params.progressive),
+                    Field::EscapeRadius => println!("{}", data.params.escape_radius),
+                    Field::Bailout => println!("{}", data.params.bailout),
+                    Field::InteriorStats => println!("{}", data.params.interior_stats),
+                    Field::Trap => println!("{}", Trap::describe(data.params.trap.as_ref())),
+                    Field::Averages => println!("{}", data.params.averages),
+                    Field::StripeDensity => println!("{}", data.params.stripe_density),
                 };
 
                 State::Prompt(data)
@@ -223,20 +292,41 @@ impl State {
                     }
                     Field::CenterX => {
                         match value.parse::<Real>() {
-                            Ok(value) => data.params.center.0 = value,
-                            Err(_) => println!("Invalid value: {}", value),
+                            Ok(real) => {
+                                let mut params = data.params.clone();
+                                params.center.0 = real;
+                                match params.check_precision() {
+                                    Ok(()) => data.params = params,
+                                    Err(e) => println!("Invalid value: {} ({})", value, e),
+                                }
+                            }
+                            Err(e) => println!("Invalid value: {} ({})", value, e),
                         };
                     }
                     Field::CenterY => {
                         match value.parse::<Real>() {
-                            Ok(value) => data.params.center.1 = value,
-                            Err(_) => println!("Invalid value: {}", value),
+                            Ok(real) => {
+                                let mut params = data.params.clone();
+                                params.center.1 = real;
+                                match params.check_precision() {
+                                    Ok(()) => data.params = params,
+                                    Err(e) => println!("Invalid value: {} ({})", value, e),
+                                }
+                            }
+                            Err(e) => println!("Invalid value: {} ({})", value, e),
                         };
                     }
                     Field::Radius => {
                         match value.parse::<Real>() {
-                            Ok(value) => data.params.radius = value,
-                            Err(_) => println!("Invalid value: {}", value),
+                            Ok(real) => {
+                                let mut params = data.params.clone();
+                                params.radius = real;
+                                match params.check_precision() {
+                                    Ok(()) => data.params = params,
+                                    Err(e) => println!("Invalid value: {} ({})", value, e),
+                                }
+                            }
+                            Err(e) => println!("Invalid value: {} ({})", value, e),
                         };
                     }
                     Field::Supersampling => {
@@ -249,6 +339,100 @@ impl State {
                         Ok(value) => data.colorfunc = value,
                         Err(e) => println!("Invalid value: {} ({})", value, e),
                     },
+                    Field::Backend => match value.parse::<Backend>() {
+                        Ok(value) => data.backend = value,
+                        Err(e) => println!("Invalid value: {} ({})", value, e),
+                    },
+                    Field::Fractal => match value.parse::<FractalKind>() {
+                        Ok(value) => data.params.fractal = value,
+                        Err(e) => println!("Invalid value: {} ({})", value, e),
+                    },
+                    Field::JuliaX => {
+                        match value.parse::<Real>() {
+                            Ok(value) => data.params.julia.0 = value,
+                            Err(_) => println!("Invalid value: {}", value),
+                        };
+                    }
+                    Field::JuliaY => {
+                        match value.parse::<Real>() {
+                            Ok(value) => data.params.julia.1 = value,
+                            Err(_) => println!("Invalid value: {}", value),
+                        };
+                    }
+                    Field::Power => {
+                        match value.parse::<f64>() {
+                            Ok(value) if value.is_finite() => data.params.power = value,
+                            _ => println!("Invalid value: {}", value),
+                        };
+                    }
+                    Field::Formula => match Formula::parse_option(&value) {
+                        Ok(value) => data.params.formula = value,
+                        Err(e) => println!("Invalid value: {} ({})", value, e),
+                    },
+                    Field::Roots => match value.parse::<Polynomial>() {
+                        Ok(value) => data.params.polynomial = value,
+                        Err(e) => println!("Invalid value: {} ({})", value, e),
+                    },
+                    Field::Samples => {
+                        match value.parse::<u64>() {
+                            Ok(value) => data.params.buddhabrot.samples = value,
+                            Err(_) => println!("Invalid value: {}", value),
+                        };
+                    }
+                    Field::Window(channel) => match value.parse::<Window>() {
+                        Ok(value) => data.params.buddhabrot.windows[channel] = value,
+                        Err(e) => println!("Invalid value: {} ({})", value, e),
+                    },
+                    Field::Sequence => match value.parse::<Sequence>() {
+                        Ok(value) => data.params.sequence = value,
+                        Err(e) => println!("Invalid value: {} ({})", value, e),
+                    },
+                    Field::Subdivision => {
+                        match value.parse::<bool>() {
+                            Ok(value) => data.params.subdivision = value,
+                            Err(_) => println!("Invalid value: {}", value),
+                        };
+                    }
+                    Field::Progressive => {
+                        match value.parse::<bool>() {
+                            Ok(value) => data.params.progressive = value,
+                            Err(_) => println!("Invalid value: {}", value),
+                        };
+                    }
+                    Field::EscapeRadius => {
+                        match value.parse::<f64>() {
+                            Ok(value) if value.is_finite() && value > 0.0 => {
+                                data.params.escape_radius = value
+                            }
+                            _ => println!("Invalid value: {}", value),
+                        };
+                    }
+                    Field::Bailout => match value.parse::<Bailout>() {
+                        Ok(value) => data.params.bailout = value,
+                        Err(e) => println!("Invalid value: {} ({})", value, e),
+                    },
+                    Field::InteriorStats => {
+                        match value.parse::<bool>() {
+                            Ok(value) => data.params.interior_stats = value,
+                            Err(_) => println!("Invalid value: {}", value),
+                        };
+                    }
+                    Field::Trap => match Trap::parse_option(&value) {
+                        Ok(value) => data.params.trap = value,
+                        Err(e) => println!("Invalid value: {} ({})", value, e),
+                    },
+                    Field::Averages => {
+                        match value.parse::<bool>() {
+                            Ok(value) => data.params.averages = value,
+                            Err(_) => println!("Invalid value: {}", value),
+                        };
+                    }
+                    Field::StripeDensity => {
+                        match value.parse::<f64>() {
+                            Ok(value) if value.is_finite() => data.params.stripe_density = value,
+                            _ => println!("Invalid value: {}", value),
+                        };
+                    }
                 };
 
                 State::Prompt(data)
@@ -261,11 +445,17 @@ impl State {
                 data.render.recalc(&data.params);
 
                 // Render
-                let job = data.render.clone().run();
+                let job = data
+                    .render
+                    .clone()
+                    .run_with_previews(data.colorfunc.clone(), data.backend);
                 match job.join_with_progress() {
                     Ok((render, _)) => {
                         // Update the render and return
                         println!("Success");
+                        if render.guessed() > 0.0 {
+                            println!("Guessed {:.1}% of the pixels.", render.guessed() * 100.0);
+                        }
                         State::Prompt(Data { render, ..data })
                     }
                     Err(msg) => {
@@ -311,6 +501,36 @@ impl State {
                     "set colorfunc {}\n",
                     data.colorfunc.info()
                 ));
+                config.push_str(&format!("set backend {}\n", data.backend));
+                config.push_str(&format!("set fractal {}\n", params.fractal));
+                config.push_str(&format!("set julia:x {}\n", params.julia.0));
+                config.push_str(&format!("set julia:y {}\n", params.julia.1));
+                config.push_str(&format!("set power {}\n", params.power));
+                config.push_str(&format!(
+                    "set formula {}\n",
+                    Formula::describe(params.formula.as_ref())
+                ));
+                config.push_str(&format!("set roots {}\n", params.polynomial));
+                config.push_str(&format!(
+                    "set buddhabrot:samples {}\n",
+                    params.buddhabrot.samples
+                ));
+                let channels = ["red", "green", "blue"];
+                for (name, window) in channels.iter().zip(&params.buddhabrot.windows) {
+                    config.push_str(&format!("set buddhabrot:{} {}\n", name, window));
+                }
+                config.push_str(&format!("set sequence {}\n", params.sequence));
+                config.push_str(&format!("set subdivision {}\n", params.subdivision));
+                config.push_str(&format!("set progressive {}\n", params.progressive));
+                config.push_str(&format!("set escape_radius {}\n", params.escape_radius));
+                config.push_str(&format!("set bailout {}\n", params.bailout));
+                config.push_str(&format!("set interior_stats {}\n", params.interior_stats));
+                config.push_str(&format!(
+                    "set trap {}\n",
+                    Trap::describe(params.trap.as_ref())
+                ));
+                config.push_str(&format!("set averages {}\n", params.averages));
+                config.push_str(&format!("set stripe_density {}\n", params.stripe_density));
 
                 // Save the string to the file
                 match fs::write(path, config) {
@@ -329,11 +549,17 @@ impl State {
     }
 }
 
-pub fn begin(_config: Config) {
+pub fn begin(config: Config) {
     let render = Render::default();
     let params = render.params.clone();
     let colorfunc = ColorFunction::greyscale();
-    let mut state: State = State::Prompt(Data { render, params, colorfunc });
+    let backend = config.backend;
+    let mut state: State = State::Prompt(Data {
+        render,
+        params,
+        colorfunc,
+        backend,
+    });
 
     // CLI Loop
     loop {
@@ -371,8 +597,45 @@ fn show_help() {
     );
     help.push_str("    supersampling (positive integer)  Factor (in both dimensions) to increase number of pixels for computation\n");
     help.push_str(
-        "    colorfunc     (string)            The color function to use when exporting image\n\n",
+        "    colorfunc     (string)            The color function to use when exporting image\n",
+    );
+    help.push_str(
+        "    backend       (string)            How to compute the render: cpu, cuda, perturbation or auto\n",
+    );
+    help.push_str(
+        "    fractal       (string)            Which fractal to render: mandelbrot, julia, burningship, tricorn,\n",
+    );
+    help.push_str("                                      celtic, buffalo, newton, buddhabrot, antibuddhabrot or\n");
+    help.push_str("                                      lyapunov\n");
+    help.push_str("    julia:x       (floating-point)    Real part of the constant c for the Julia set\n");
+    help.push_str(
+        "    julia:y       (floating-point)    Imaginary part of the constant c for the Julia set\n",
+    );
+    help.push_str(
+        "    power         (floating-point)    Power d in the iteration z^d + c (may be negative)\n",
     );
+    help.push_str("    formula       (string)            Formula to iterate z with instead, e.g. z^3-0.5*z+c, using\n");
+    help.push_str("                                      + - * / ^, exp, log, sin, cos, z, c and i, or none\n");
+    help.push_str("    roots         (string)            Roots of the Newton fractal's polynomial, as re,im pairs\n");
+    help.push_str("                                      separated by semicolons, e.g. 1;-0.5,0.866;-0.5,-0.866\n");
+    help.push_str("    buddhabrot:samples\n");
+    help.push_str("                  (positive integer)  Number of orbits the Buddhabrot samples\n");
+    help.push_str("    buddhabrot:red, buddhabrot:green, buddhabrot:blue\n");
+    help.push_str("                  (string)            Range of iterations counted in each channel, as min,max\n");
+    help.push_str("    sequence      (string)            Sequence of the Lyapunov fractal's rates, e.g. AB or AABAB\n");
+    help.push_str("    subdivision   (true/false)        Fill in areas whose edges all match without iterating them\n");
+    help.push_str("                                      (CPU backend only)\n");
+    help.push_str("    progressive   (true/false)        Render every 8th pixel, then every 4th, 2nd and all of them,\n");
+    help.push_str("                                      showing a preview after each pass\n");
+    help.push_str("    escape_radius (floating-point)    How far z has to get to count as diverged (default 2)\n");
+    help.push_str("    bailout       (string)            How z is measured against the escape radius: norm, real,\n");
+    help.push_str("                                      imag or manhattan\n");
+    help.push_str("    interior_stats (true/false)       Record the smallest |z| and the multiplier of the cycle for\n");
+    help.push_str("                                      pixels inside the set, for the interior color functions\n");
+    help.push_str("    trap          (string)            Orbit trap for the trap color function: point(x,y), line(x,y,angle),\n");
+    help.push_str("                                      cross(x,y), circle(x,y,radius), image(path,x,y,width) or none\n");
+    help.push_str("    averages      (true/false)        Keep the stripe and triangle inequality averages of each orbit\n");
+    help.push_str("    stripe_density (floating-point)   Number of stripes for each turn of z, for the stripe average\n\n");
     help.push_str("  Color Functions:\n");
     help.push_str(
         "    greyscale            Black center, value determined by number of iterations\n",
@@ -386,5 +649,41 @@ fn show_help() {
     help.push_str(
         "    red(shift, scale)    Red colorized, with given shift (pos. int.) and scale (float)\n",
     );
+    help.push_str(
+        "    roots(scale)         Newton fractal, hue by root and darker with more iterations (float)\n",
+    );
+    help.push_str(
+        "    lyapunov(scale)      Lyapunov fractal, gold where stable and blue where chaotic (float)\n",
+    );
+    help.push_str(
+        "    period               Inside colored by the period of each pixel's orbit, outside greyscale\n",
+    );
+    help.push_str(
+        "    distance(scale)      Outside shaded by distance to the set, over about scale pixels (float)\n",
+    );
+    help.push_str(
+        "    boundary(shift, scale) Same as color(shift, scale), with the edge of the set highlighted\n",
+    );
+    help.push_str(
+        "    minimum(scale)       Inside colored by when and how close its orbit came to zero (float)\n",
+    );
+    help.push_str(
+        "    multiplier           Same as period, brighter towards the edge of each component\n",
+    );
+    help.push_str(
+        "    trap(scale)          Brighter the closer each orbit came to the orbit trap (float)\n",
+    );
+    help.push_str(
+        "    stripes(shift, scale) Colorized by the stripe average, with given shift and scale\n",
+    );
+    help.push_str(
+        "    triangle(shift, scale) Colorized by the triangle inequality average, with given shift and scale\n",
+    );
+    help.push_str(
+        "    shade(angle, height, colorfunc) Any color function, lit as a surface by a light at the given\n",
+    );
+    help.push_str(
+        "                         angle (degrees) and height\n",
+    );
     println!("{}", help);
 }
@@ -4,6 +4,12 @@ use std::rc::Rc;
 use std::str::FromStr;
 
 use crate::math::*;
+use crate::render::{Average, Parameters, PixelDetails};
+
+// How tight the specular highlight of shade(...) is, as the Blinn-Phong exponent, and how much of it
+// is added on top of the lit color
+const SHININESS: i32 = 20;
+const SPECULAR: f64 = 0.3;
 
 #[derive(Copy, Clone)]
 // A color can be either RGB or HSV, each represented by 3 floating-point values
@@ -12,10 +18,34 @@ pub enum Color {
     HSV(f32, f32, f32),
 }
 
-// TODO add converstion between RGB and HSV
+impl Color {
+    // Red, green and blue components of the color.  HSV colors have their hue in degrees, and
+    // their saturation and value between 0 and 1.
+    pub fn rgb(self) -> (f32, f32, f32) {
+        match self {
+            Color::RGB(r, g, b) => (r, g, b),
+            Color::HSV(h, s, v) => {
+                let h = (h / 60.0).rem_euclid(6.0);
+                let chroma = v * s;
+                let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+                let (r, g, b) = match h as u32 {
+                    0 => (chroma, x, 0.0),
+                    1 => (x, chroma, 0.0),
+                    2 => (0.0, chroma, x),
+                    3 => (0.0, x, chroma),
+                    4 => (x, 0.0, chroma),
+                    _ => (chroma, 0.0, x),
+                };
+                let m = v - chroma;
+                (r + m, g + m, b + m)
+            }
+        }
+    }
+}
 
-// The type of closure that maps a rendered pixel to a color
-type Func = Rc<dyn Fn(u32, u32, Complex) -> Color>;
+// The type of closure that maps a rendered pixel (its iterations, z and details, along with the
+// max iterations and the render's parameters) to a color
+type Func = Rc<dyn Fn(u32, u32, Complex, &PixelDetails, &Parameters) -> Color>;
 
 #[derive(Clone)]
 // Wrapper struct for mapping function
@@ -38,6 +68,12 @@ impl FromStr for ColorFunction {
         } else if s == "rgreyscale" {
             // Reversed greyscale function
             Ok(ColorFunction::rgreyscale())
+        } else if s == "period" {
+            // Interior colored by period
+            Ok(ColorFunction::period())
+        } else if s == "multiplier" {
+            // Interior colored by period and the multiplier of the cycle
+            Ok(ColorFunction::multiplier())
         } else if s.starts_with("color(") && s.ends_with(")") {
             // Color function, with two given parameters, shift and scale
 
@@ -88,12 +124,232 @@ impl FromStr for ColorFunction {
 
                 Ok(ColorFunction::red(shift, scale))
             }
+        } else if s.starts_with("roots(") && s.ends_with(")") {
+            // Newton fractal color function, with one parameter, scale
+
+            // Remove "roots(" and ")", leaving just the parameter
+            let end = s.len() - 1;
+            let param = s[6..end].trim();
+
+            match param.parse::<f64>() {
+                Ok(scale) => Ok(ColorFunction::roots(scale)),
+                Err(e) => Err(format!("Couldn't parse scale {}: {:?}.", param, e)),
+            }
+        } else if s.starts_with("distance(") && s.ends_with(")") {
+            // Distance estimate color function, with one parameter, scale
+
+            // Remove "distance(" and ")", leaving just the parameter
+            let end = s.len() - 1;
+            let param = s[9..end].trim();
+
+            match param.parse::<f64>() {
+                Ok(scale) => Ok(ColorFunction::distance(scale)),
+                Err(e) => Err(format!("Couldn't parse scale {}: {:?}.", param, e)),
+            }
+        } else if s.starts_with("boundary(") && s.ends_with(")") {
+            // Color function with the boundary highlighted, with two parameters, shift and scale
+
+            // Remove "boundary(" and ")", leaving just the parameters
+            let end = s.len() - 1;
+            let param_str = &s[9..end].to_string();
+
+            // Isolate the parameters
+            let params: Vec<_> = param_str.split(",").collect();
+
+            if params.len() != 2 {
+                Err("Incorrect syntax: boundary has 2 parameters (shift and scale).".to_string())
+            } else {
+                // Parse the parameters into numerical values
+                let shift = match params[0].trim().parse::<u32>() {
+                    Ok(value) => value,
+                    Err(e) => return Err(format!("Couldn't parse shift {}: {:?}.", params[0], e)),
+                };
+                let scale = match params[1].trim().parse::<f64>() {
+                    Ok(value) => value,
+                    Err(e) => return Err(format!("Couldn't parse scale {}: {:?}.", params[1], e)),
+                };
+
+                Ok(ColorFunction::boundary(shift, scale))
+            }
+        } else if s.starts_with("minimum(") && s.ends_with(")") {
+            // Interior colored by the smallest |z|, with one parameter, scale
+
+            // Remove "minimum(" and ")", leaving just the parameter
+            let end = s.len() - 1;
+            let param = s[8..end].trim();
+
+            match param.parse::<f64>() {
+                Ok(scale) => Ok(ColorFunction::minimum(scale)),
+                Err(e) => Err(format!("Couldn't parse scale {}: {:?}.", param, e)),
+            }
+        } else if s.starts_with("trap(") && s.ends_with(")") {
+            // Orbit trap color function, with one parameter, scale
+
+            // Remove "trap(" and ")", leaving just the parameter
+            let end = s.len() - 1;
+            let param = s[5..end].trim();
+
+            match param.parse::<f64>() {
+                Ok(scale) => Ok(ColorFunction::trap(scale)),
+                Err(e) => Err(format!("Couldn't parse scale {}: {:?}.", param, e)),
+            }
+        } else if s.starts_with("stripes(") && s.ends_with(")") {
+            // Stripe average color function, with two parameters, shift and scale
+
+            // Remove "stripes(" and ")", leaving just the parameters
+            let end = s.len() - 1;
+            let param_str = &s[8..end].to_string();
+
+            // Isolate the parameters
+            let params: Vec<_> = param_str.split(",").collect();
+
+            if params.len() != 2 {
+                Err("Incorrect syntax: stripes has 2 parameters (shift and scale).".to_string())
+            } else {
+                // Parse the parameters into numerical values
+                let shift = match params[0].trim().parse::<u32>() {
+                    Ok(value) => value,
+                    Err(e) => return Err(format!("Couldn't parse shift {}: {:?}.", params[0], e)),
+                };
+                let scale = match params[1].trim().parse::<f64>() {
+                    Ok(value) => value,
+                    Err(e) => return Err(format!("Couldn't parse scale {}: {:?}.", params[1], e)),
+                };
+
+                Ok(ColorFunction::stripes(shift, scale))
+            }
+        } else if s.starts_with("triangle(") && s.ends_with(")") {
+            // Triangle inequality average color function, with two parameters, shift and scale
+
+            // Remove "triangle(" and ")", leaving just the parameters
+            let end = s.len() - 1;
+            let param_str = &s[9..end].to_string();
+
+            // Isolate the parameters
+            let params: Vec<_> = param_str.split(",").collect();
+
+            if params.len() != 2 {
+                Err("Incorrect syntax: triangle has 2 parameters (shift and scale).".to_string())
+            } else {
+                // Parse the parameters into numerical values
+                let shift = match params[0].trim().parse::<u32>() {
+                    Ok(value) => value,
+                    Err(e) => return Err(format!("Couldn't parse shift {}: {:?}.", params[0], e)),
+                };
+                let scale = match params[1].trim().parse::<f64>() {
+                    Ok(value) => value,
+                    Err(e) => return Err(format!("Couldn't parse scale {}: {:?}.", params[1], e)),
+                };
+
+                Ok(ColorFunction::triangle(shift, scale))
+            }
+        } else if s.starts_with("shade(") && s.ends_with(")") {
+            // Lighting on top of another color function, with three parameters, angle, height and
+            // the color function
+
+            // Remove "shade(" and ")", leaving just the parameters
+            let end = s.len() - 1;
+            let param_str = &s[6..end].to_string();
+
+            // Isolate the parameters, leaving any commas in the color function alone
+            let params: Vec<_> = param_str.splitn(3, ",").collect();
+
+            if params.len() != 3 {
+                Err("Incorrect syntax: shade has 3 parameters (angle, height and color function)."
+                    .to_string())
+            } else {
+                // Parse the parameters into numerical values
+                let angle = match params[0].trim().parse::<f64>() {
+                    Ok(value) => value,
+                    Err(e) => return Err(format!("Couldn't parse angle {}: {:?}.", params[0], e)),
+                };
+                let height = match params[1].trim().parse::<f64>() {
+                    Ok(value) => value,
+                    Err(e) => return Err(format!("Couldn't parse height {}: {:?}.", params[1], e)),
+                };
+                let base = params[2].parse::<ColorFunction>()?;
+
+                Ok(ColorFunction::shade(angle, height, base))
+            }
+        } else if s.starts_with("lyapunov(") && s.ends_with(")") {
+            // Lyapunov fractal color function, with one parameter, scale
+
+            // Remove "lyapunov(" and ")", leaving just the parameter
+            let end = s.len() - 1;
+            let param = s[9..end].trim();
+
+            match param.parse::<f64>() {
+                Ok(scale) => Ok(ColorFunction::lyapunov(scale)),
+                Err(e) => Err(format!("Couldn't parse scale {}: {:?}.", param, e)),
+            }
         } else {
             Err(format!("No such color function: {}.", s))
         }
     }
 }
 
+// Fraction of an iteration to take off a diverged pixel's count, so that colors blend smoothly
+// between iterations.  z grows by a power of the degree on each iteration, so this depends on it,
+// and on how far past the escape radius z got.  The larger the radius, the smoother the result.
+// Bailouts other than the norm can let z escape while |z| is still inside the radius (or even
+// inside the unit circle, which gives a NaN), so it's kept between 0 and 2.
+fn smoothing(z: Complex, params: &Parameters) -> f64 {
+    let degree = params.power.abs();
+    let radius = params.escape_radius.ln();
+    if degree <= 1.0 || radius <= 0.0 {
+        return 0.0;
+    }
+
+    let smoothed = (z.abs().ln() / radius).ln() / degree.ln();
+    if smoothed.is_nan() {
+        0.0
+    } else {
+        smoothed.clamp(0.0, 2.0)
+    }
+}
+
+// Estimated distance from a diverged pixel to the edge of the set, in pixels, from
+// |z| ln|z| / |dz/dc|.  It's infinite where the derivative wasn't tracked.
+fn distance(z: Complex, details: &PixelDetails, params: &Parameters) -> f64 {
+    let size = z.abs();
+    size * size.ln() / details.derivative.abs() / params.pixel_size()
+}
+
+// Read a palette of 2048 colors from a file, one per line as r,g,b from 0 to 255
+fn palette(path: &str) -> Vec<Color> {
+    let file = File::open(path).unwrap();
+    BufReader::new(file)
+        .lines()
+        .map(|line| {
+            let rgb: Vec<_> = line
+                .unwrap()
+                .split(",")
+                .map(|s| s.parse::<u8>().unwrap() as f32 / 255.0)
+                .collect();
+            Color::RGB(rgb[0], rgb[1], rgb[2])
+        })
+        .collect()
+}
+
+// Color a diverged pixel from the palette by one of its averages, blended between its last two
+// iterations, scaled and shifted along the palette.  Pixels without the average are black.
+fn average_color(
+    colors: &[Color],
+    average: Option<Average>,
+    (shift, scale): (u32, f64),
+    z: Complex,
+    params: &Parameters,
+) -> Color {
+    match average {
+        Some(average) if average.count > 0 => {
+            let value = average.smooth(smoothing(z, params));
+            let idx = (value * scale + shift as f64) as i64;
+            colors[idx.rem_euclid(colors.len() as i64) as usize]
+        }
+        _ => Color::RGB(0.0, 0.0, 0.0),
+    }
+}
+
 impl ColorFunction {
     pub fn new(func: Func, name: String) -> ColorFunction {
         ColorFunction { name, func }
@@ -110,25 +366,13 @@ impl ColorFunction {
 
     pub fn color(shift: u32, scale: f64) -> ColorFunction {
         // Read colors from file
-        let file = File::open("colors.csv").unwrap();
-        let colors: Vec<_> = BufReader::new(file)
-            .lines()
-            .map(|line| {
-                let rgb: Vec<_> = line
-                    .unwrap()
-                    .split(",")
-                    .map(|s| s.parse::<u8>().unwrap() as f32 / 255.0)
-                    .collect();
-                Color::RGB(rgb[0], rgb[1], rgb[2])
-            })
-            .collect();
+        let colors = palette("colors.csv");
         ColorFunction::new(
-            Rc::new(move |i: u32, m: u32, z: Complex| -> Color {
+            Rc::new(move |i: u32, m: u32, z: Complex, _: &PixelDetails, params: &Parameters| {
                 if i == m {
                     Color::RGB(0.0, 0.0, 0.0)
                 } else {
-                    let size = z.abs();
-                    let mut smoothed = size.log(2.0).log(2.0);
+                    let mut smoothed = smoothing(z, params);
                     if smoothed > 2.0 {
                         smoothed = 2.0;
                     }
@@ -142,25 +386,13 @@ impl ColorFunction {
 
     pub fn red(shift: u32, scale: f64) -> ColorFunction {
         // Read colors from file
-        let file = File::open("red.csv").unwrap();
-        let colors: Vec<_> = BufReader::new(file)
-            .lines()
-            .map(|line| {
-                let rgb: Vec<_> = line
-                    .unwrap()
-                    .split(",")
-                    .map(|s| s.parse::<u8>().unwrap() as f32 / 255.0)
-                    .collect();
-                Color::RGB(rgb[0], rgb[1], rgb[2])
-            })
-            .collect();
+        let colors = palette("red.csv");
         ColorFunction::new(
-            Rc::new(move |i: u32, m: u32, z: Complex| -> Color {
+            Rc::new(move |i: u32, m: u32, z: Complex, _: &PixelDetails, params: &Parameters| {
                 if i == m {
                     Color::RGB(0.0, 0.0, 0.0)
                 } else {
-                    let size = z.abs();
-                    let mut smoothed = size.log(2.0).log(2.0);
+                    let mut smoothed = smoothing(z, params);
                     if smoothed > 2.0 {
                         smoothed = 2.0;
                     }
@@ -172,9 +404,270 @@ impl ColorFunction {
         )
     }
 
+    // Color each pixel by which of the Newton fractal's roots it reached, getting darker the more
+    // iterations it took, at a rate given by the scale
+    pub fn roots(scale: f64) -> ColorFunction {
+        ColorFunction::new(
+            Rc::new(
+                move |i: u32, _: u32, _: Complex, details: &PixelDetails, params: &Parameters| {
+                    match details.root {
+                        Some(root) => {
+                            let roots = params.polynomial.roots().len() as f32;
+                            let hue = root as f32 / roots * 360.0;
+                            let value = (-(i as f64) * scale).exp() as f32;
+                            Color::HSV(hue, 0.8, value)
+                        }
+                        None => Color::RGB(0.0, 0.0, 0.0),
+                    }
+                },
+            ),
+            format!("roots({})", scale),
+        )
+    }
+
+    // Shade the outside of the set by its distance estimate, from black at the edge of the set to
+    // white further away, over roughly `scale` pixels.  Filaments too thin for the iteration count
+    // to show still come out as dark lines, and the inside is black.
+    pub fn distance(scale: f64) -> ColorFunction {
+        ColorFunction::new(
+            Rc::new(
+                move |i: u32, m: u32, z: Complex, details: &PixelDetails, params: &Parameters| {
+                    if i == m {
+                        Color::RGB(0.0, 0.0, 0.0)
+                    } else {
+                        let p = (distance(z, details, params) / scale).tanh() as f32;
+                        Color::RGB(p, p, p)
+                    }
+                },
+            ),
+            format!("distance({})", scale),
+        )
+    }
+
+    // Same as color(shift, scale), but with the pixels within about a pixel of the edge of the set
+    // (by the distance estimate) blended towards white, to highlight the boundary
+    pub fn boundary(shift: u32, scale: f64) -> ColorFunction {
+        let color = ColorFunction::color(shift, scale).func;
+        ColorFunction::new(
+            Rc::new(
+                move |i: u32, m: u32, z: Complex, details: &PixelDetails, params: &Parameters| {
+                    let base = color(i, m, z, details, params);
+                    if i == m {
+                        return base;
+                    }
+
+                    let glow = (-distance(z, details, params)).exp() as f32;
+                    let (r, g, b) = base.rgb();
+                    Color::RGB(
+                        r + (1.0 - r) * glow,
+                        g + (1.0 - g) * glow,
+                        b + (1.0 - b) * glow,
+                    )
+                },
+            ),
+            format!("boundary({}, {})", shift, scale),
+        )
+    }
+
+    // Light the outside of the set as if it were a surface, colored by the given color function,
+    // with the light coming from `angle` degrees round from the right and `height` up from the
+    // image.  The surface's normal comes from z / (dz/dc), which points away from the set, and it's
+    // lit with Lambert's diffuse lighting and a Blinn-Phong highlight.  The inside of the set, and
+    // pixels without a derivative, keep the color function's own color.
+    pub fn shade(angle: f64, height: f64, base: ColorFunction) -> ColorFunction {
+        let color = base.func;
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let length = (1.0 + height * height).sqrt();
+        let light = (cos / length, sin / length, height / length);
+
+        // Halfway between the light and the direction the image is looked at from, straight down
+        let half = (light.0, light.1, light.2 + 1.0);
+        let length = (half.0 * half.0 + half.1 * half.1 + half.2 * half.2).sqrt();
+        let half = (half.0 / length, half.1 / length, half.2 / length);
+
+        ColorFunction::new(
+            Rc::new(
+                move |i: u32, m: u32, z: Complex, details: &PixelDetails, params: &Parameters| {
+                    let base = color(i, m, z, details, params);
+                    let slope = z / details.derivative;
+                    let size = slope.abs();
+                    if i == m || !size.is_finite() || size == 0.0 {
+                        return base;
+                    }
+
+                    // The normal leans away from the set at 45 degrees
+                    let normal = (
+                        slope.0 / size / 2f64.sqrt(),
+                        slope.1 / size / 2f64.sqrt(),
+                        1.0 / 2f64.sqrt(),
+                    );
+                    let dot = |(x, y, z): (f64, f64, f64)| {
+                        (normal.0 * x + normal.1 * y + normal.2 * z).max(0.0)
+                    };
+                    let diffuse = dot(light) as f32;
+                    let specular = (dot(half).powi(SHININESS) * SPECULAR) as f32;
+
+                    let (r, g, b) = base.rgb();
+                    let lit = |c: f32| (c * diffuse + specular).min(1.0);
+                    Color::RGB(lit(r), lit(g), lit(b))
+                },
+            ),
+            format!("shade({}, {}, {})", angle, height, base.name),
+        )
+    }
+
+    // Color the Lyapunov fractal's stable regions, where its exponent is negative, in gold, and its
+    // chaotic regions in blue, both getting brighter the further the exponent is from zero, at a
+    // rate given by the scale
+    pub fn lyapunov(scale: f64) -> ColorFunction {
+        ColorFunction::new(
+            Rc::new(
+                move |_: u32, _: u32, _: Complex, details: &PixelDetails, _: &Parameters| {
+                    let exponent = details.exponent;
+                    let p = (1.0 - (-exponent.abs() * scale).exp()) as f32;
+                    if exponent < 0.0 {
+                        Color::RGB(p, p * 0.8, 0.0)
+                    } else if exponent > 0.0 {
+                        Color::RGB(0.0, p * 0.3, p)
+                    } else {
+                        Color::RGB(0.0, 0.0, 0.0)
+                    }
+                },
+            ),
+            format!("lyapunov({})", scale),
+        )
+    }
+
+    // Color the inside of the set by the period of each pixel's orbit, stepping round the hues by the
+    // golden angle so that neighbouring periods stand out from each other.  The outside is the same
+    // as greyscale, and inside pixels whose period wasn't found are black.
+    pub fn period() -> ColorFunction {
+        ColorFunction::new(
+            Rc::new(|i: u32, m: u32, _: Complex, details: &PixelDetails, _: &Parameters| {
+                match details.period {
+                    Some(period) => Color::HSV((period - 1) as f32 * 137.5, 0.7, 0.9),
+                    None if i == m => Color::RGB(0.0, 0.0, 0.0),
+                    None => {
+                        let p = 1.0 - i as f32 / m as f32;
+                        Color::RGB(p, p, p)
+                    }
+                }
+            }),
+            "period".to_string(),
+        )
+    }
+
+    // Color the inside of the set by the iteration its orbit came closest to zero at, stepping round
+    // the hues by the golden angle, and get brighter the further from zero that was, at a rate given
+    // by the scale.  It needs interior stats, without which the inside is black, and the outside is
+    // the same as greyscale.
+    pub fn minimum(scale: f64) -> ColorFunction {
+        ColorFunction::new(
+            Rc::new(
+                move |i: u32, m: u32, _: Complex, details: &PixelDetails, _: &Parameters| {
+                    match details.minimum {
+                        Some((size, n)) if i == m => {
+                            let value = (size * scale).tanh() as f32;
+                            Color::HSV((n - 1) as f32 * 137.5, 0.7, value)
+                        }
+                        _ if i == m => Color::RGB(0.0, 0.0, 0.0),
+                        _ => {
+                            let p = 1.0 - i as f32 / m as f32;
+                            Color::RGB(p, p, p)
+                        }
+                    }
+                },
+            ),
+            format!("minimum({})", scale),
+        )
+    }
+
+    // Same as period, but with each component of the inside getting brighter from its nucleus,
+    // where the multiplier of the cycle is 0, out to its edge, where it's 1.  It needs interior
+    // stats, without which the inside is black.
+    pub fn multiplier() -> ColorFunction {
+        ColorFunction::new(
+            Rc::new(|i: u32, m: u32, _: Complex, details: &PixelDetails, _: &Parameters| {
+                match (details.period, details.multiplier) {
+                    (Some(period), Some(multiplier)) => {
+                        let value = multiplier.min(1.0) as f32;
+                        Color::HSV((period - 1) as f32 * 137.5, 0.7, value)
+                    }
+                    _ if i == m => Color::RGB(0.0, 0.0, 0.0),
+                    _ => {
+                        let p = 1.0 - i as f32 / m as f32;
+                        Color::RGB(p, p, p)
+                    }
+                }
+            }),
+            "multiplier".to_string(),
+        )
+    }
+
+    // Color every pixel by how close its orbit came to the trap, from white where it went right
+    // through it to black further away, at a rate given by the scale.  An image trap shows its own
+    // color where the orbit landed on it.  Without a trap, everything is black.
+    pub fn trap(scale: f64) -> ColorFunction {
+        ColorFunction::new(
+            Rc::new(
+                move |_: u32, _: u32, _: Complex, details: &PixelDetails, params: &Parameters| {
+                    match (&params.trap, details.trap) {
+                        (Some(trap), Some((distance, z))) => match trap.color(z) {
+                            Some((r, g, b)) if distance == 0.0 => Color::RGB(r, g, b),
+                            _ => {
+                                let p = (-distance * scale).exp() as f32;
+                                Color::RGB(p, p, p)
+                            }
+                        },
+                        _ => Color::RGB(0.0, 0.0, 0.0),
+                    }
+                },
+            ),
+            format!("trap({})", scale),
+        )
+    }
+
+    // Color the outside of the set from the palette by the stripe average of each orbit, the
+    // average of sin(arg z) (with the render's stripe density), which shows the stripes that bend
+    // around the set.  It needs the render to keep averages, and works best with a large escape
+    // radius.  The inside is black.
+    pub fn stripes(shift: u32, scale: f64) -> ColorFunction {
+        let colors = palette("colors.csv");
+        ColorFunction::new(
+            Rc::new(
+                move |i: u32, m: u32, z: Complex, details: &PixelDetails, params: &Parameters| {
+                    if i == m {
+                        Color::RGB(0.0, 0.0, 0.0)
+                    } else {
+                        average_color(&colors, details.stripe, (shift, scale), z, params)
+                    }
+                },
+            ),
+            format!("stripes({}, {})", shift, scale),
+        )
+    }
+
+    // Same as stripes(shift, scale), but by the triangle inequality average of each orbit, the
+    // average of where |z| falls between the bounds that the triangle inequality gives it
+    pub fn triangle(shift: u32, scale: f64) -> ColorFunction {
+        let colors = palette("colors.csv");
+        ColorFunction::new(
+            Rc::new(
+                move |i: u32, m: u32, z: Complex, details: &PixelDetails, params: &Parameters| {
+                    if i == m {
+                        Color::RGB(0.0, 0.0, 0.0)
+                    } else {
+                        average_color(&colors, details.triangle, (shift, scale), z, params)
+                    }
+                },
+            ),
+            format!("triangle({}, {})", shift, scale),
+        )
+    }
+
     pub fn greyscale() -> ColorFunction {
         ColorFunction::new(
-            Rc::new(|i: u32, m: u32, _: Complex| -> Color {
+            Rc::new(|i: u32, m: u32, _: Complex, _: &PixelDetails, _: &Parameters| -> Color {
                 if i == m {
                     Color::RGB(0.0, 0.0, 0.0)
                 } else {
@@ -188,7 +681,7 @@ impl ColorFunction {
 
     pub fn rgreyscale() -> ColorFunction {
         ColorFunction::new(
-            Rc::new(|i: u32, m: u32, _: Complex| -> Color {
+            Rc::new(|i: u32, m: u32, _: Complex, _: &PixelDetails, _: &Parameters| -> Color {
                 if i == m {
                     Color::RGB(1.0, 1.0, 1.0)
                 } else {
@@ -200,3 +693,159 @@ impl ColorFunction {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trap::Trap;
+
+    // Parse a color function, and check that it describes itself the same way
+    fn round_trip(s: &str) -> ColorFunction {
+        let func: ColorFunction = s.parse().unwrap();
+        assert_eq!(func.info(), s);
+        assert_eq!(func.info().parse::<ColorFunction>().unwrap().info(), s);
+        func
+    }
+
+    // Color a pixel with 100 max iterations, and with the default parameters
+    fn paint(func: &ColorFunction, i: u32, details: &PixelDetails) -> (f32, f32, f32) {
+        (func.func)(i, 100, Complex(0.0, 0.0), details, &Parameters::default()).rgb()
+    }
+
+    #[test]
+    fn hsv_covers_every_sector() {
+        // Each sector of 60 degrees ramps one component up or down, at full saturation and value
+        let hues = [
+            (0.0, (1.0, 0.0, 0.0)),
+            (30.0, (1.0, 0.5, 0.0)),
+            (90.0, (0.5, 1.0, 0.0)),
+            (150.0, (0.0, 1.0, 0.5)),
+            (210.0, (0.0, 0.5, 1.0)),
+            (270.0, (0.5, 0.0, 1.0)),
+            (330.0, (1.0, 0.0, 0.5)),
+            (360.0, (1.0, 0.0, 0.0)),
+            (-90.0, (0.5, 0.0, 1.0)),
+        ];
+        for &(hue, rgb) in &hues {
+            assert_eq!(Color::HSV(hue, 1.0, 1.0).rgb(), rgb, "{}", hue);
+        }
+
+        // Less saturation mixes in white, and less value darkens it
+        assert_eq!(Color::HSV(120.0, 0.5, 1.0).rgb(), (0.5, 1.0, 0.5));
+        assert_eq!(Color::HSV(240.0, 1.0, 0.5).rgb(), (0.0, 0.0, 0.5));
+        assert_eq!(Color::RGB(0.1, 0.2, 0.3).rgb(), (0.1, 0.2, 0.3));
+    }
+
+    #[test]
+    fn interior_functions_round_trip() {
+        let period = round_trip("period");
+        let minimum = round_trip("minimum(2.5)");
+        let multiplier = round_trip("multiplier");
+        assert_eq!(" minimum( 4 ) ".parse::<ColorFunction>().unwrap().info(), "minimum(4)");
+        assert!("minimum(x)".parse::<ColorFunction>().is_err());
+
+        // Inside pixels are black without their stats, and the outside is greyscale
+        let outside = PixelDetails::default();
+        for func in &[&period, &minimum, &multiplier] {
+            assert_eq!(paint(func, 100, &outside), (0.0, 0.0, 0.0));
+            assert_eq!(paint(func, 75, &outside), (0.25, 0.25, 0.25));
+        }
+
+        // Period 1 is red, and the multiplier takes the brightness from the nucleus to the edge
+        let inside = PixelDetails {
+            period: Some(1),
+            minimum: Some((0.0, 1)),
+            multiplier: Some(0.5),
+            ..PixelDetails::default()
+        };
+        assert_eq!(paint(&period, 100, &inside), Color::HSV(0.0, 0.7, 0.9).rgb());
+        assert_eq!(paint(&minimum, 100, &inside), (0.0, 0.0, 0.0));
+        assert_eq!(paint(&multiplier, 100, &inside), Color::HSV(0.0, 0.7, 0.5).rgb());
+    }
+
+    #[test]
+    fn trap_round_trips() {
+        let trap = round_trip("trap(0.5)");
+        assert!("trap()".parse::<ColorFunction>().is_err());
+
+        // Pixels get darker the further their orbits kept from the trap, and are black without one
+        let details = PixelDetails {
+            trap: Some((2.0, Complex(0.0, 0.0))),
+            ..PixelDetails::default()
+        };
+        let params = Parameters {
+            trap: Some(Trap::Point(Complex(0.0, 0.0))),
+            ..Parameters::default()
+        };
+        let p = (-1f64).exp() as f32;
+        assert_eq!((trap.func)(5, 100, Complex(0.0, 0.0), &details, &params).rgb(), (p, p, p));
+        assert_eq!(paint(&trap, 5, &details), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn averages_round_trip() {
+        let stripes = round_trip("stripes(0, 10)");
+        let triangle = round_trip("triangle(100, 2.5)");
+        assert!("stripes(0)".parse::<ColorFunction>().is_err());
+        assert!("triangle(-1, 2)".parse::<ColorFunction>().is_err());
+
+        // A single term isn't blended with anything, so it picks its color straight off the palette
+        let average = Some(Average {
+            sum: 0.5,
+            last: 0.5,
+            count: 1,
+        });
+        let details = PixelDetails {
+            stripe: average,
+            triangle: average,
+            ..PixelDetails::default()
+        };
+        let colors = palette("colors.csv");
+        assert_eq!(paint(&stripes, 5, &details), colors[5].rgb());
+        assert_eq!(paint(&triangle, 5, &details), colors[101].rgb());
+
+        // The inside, and pixels without the average, are black
+        assert_eq!(paint(&stripes, 100, &details), (0.0, 0.0, 0.0));
+        assert_eq!(paint(&triangle, 5, &PixelDetails::default()), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn shade_round_trips() {
+        round_trip("shade(45, 1.5, stripes(0, 10))");
+        round_trip("shade(0, 2, shade(90, 0.5, color(3, 1.5)))");
+        for s in &["shade(45, 1.5)", "shade(x, 1, greyscale)", "shade(45, 1.5, nothing)"] {
+            assert!(s.parse::<ColorFunction>().is_err(), "{}", s);
+        }
+
+        // The slope faces the light from the right, and faces away from the light from the left
+        let details = PixelDetails {
+            derivative: Complex(1.0, 0.0),
+            ..PixelDetails::default()
+        };
+        let params = Parameters::default();
+        let lit = |s: &str, i: u32| {
+            let func = s.parse::<ColorFunction>().unwrap();
+            (func.func)(i, 100, Complex(1.0, 0.0), &details, &params).rgb()
+        };
+        assert!(lit("shade(0, 1, greyscale)", 75).0 > 0.25);
+        assert!(lit("shade(180, 1, greyscale)", 75).0 < 0.25);
+
+        // The inside, and pixels without a derivative, keep the base color
+        assert_eq!(lit("shade(0, 1, greyscale)", 100), (0.0, 0.0, 0.0));
+        let shade = round_trip("shade(0, 1, greyscale)");
+        assert_eq!(paint(&shade, 75, &PixelDetails::default()), (0.25, 0.25, 0.25));
+    }
+
+    #[test]
+    fn smoothing_stays_between_0_and_2() {
+        let params = Parameters::default();
+        let expected = (3f64.ln() / 2f64.ln()).ln() / 2f64.ln();
+        assert_eq!(smoothing(Complex(3.0, 0.0), &params), expected);
+
+        // z can escape with other bailouts while |z| is inside the radius or the unit circle
+        assert_eq!(smoothing(Complex(0.5, 0.0), &params), 0.0);
+        assert_eq!(smoothing(Complex(1.5, 0.0), &params), 0.0);
+        assert_eq!(smoothing(Complex(0.0, 0.0), &params), 0.0);
+        assert_eq!(smoothing(Complex(1e300, 0.0), &params), 2.0);
+    }
+}
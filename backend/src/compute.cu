@@ -9,18 +9,101 @@ __device__ Real add(Real a, Real b) { return a + b; }
 __device__ Real sub(Real a, Real b) { return a - b; }
 __device__ Real mag_sq(Complex c) { return add(mul(c.real, c.real), mul(c.imag, c.imag)); }
 
-__device__ Complex f(Complex z, Complex c) {
-	Real real = add(sub(mul(z.real, z.real), mul(z.imag, z.imag)), c.real);
-	Real w = mul(z.real, z.imag);
-	Real imag = add(add(w, w), c.imag);
+__device__ Complex cmul(Complex a, Complex b) {
+	return { sub(mul(a.real, b.real), mul(a.imag, b.imag)), add(mul(a.real, b.imag), mul(a.imag, b.real)) };
+}
+
+// Raise z to the given power, which is an integer if it has no fractional part.  Zero to a
+// negative power is zero, rather than infinity.
+__device__ Complex cpow(Complex z, Real power) {
+	bool zero = z.real == 0.0 && z.imag == 0.0;
+	if (zero && power < 0) { return z; }
+
+	if (power == trunc(power) && fabs(power) <= 2147483647.0) {
+		// Square and multiply, taking the reciprocal at the end for negative powers
+		long n = (long)fabs(power);
+		Complex result = { 1.0, 0.0 };
+		Complex base = z;
+		while (n > 0) {
+			if (n & 1) { result = cmul(result, base); }
+			base = cmul(base, base);
+			n >>= 1;
+		}
+
+		if (power < 0) {
+			Real m = mag_sq(result);
+			result = { result.real / m, -result.imag / m };
+		}
+
+		return result;
+	}
+
+	// Use the polar form for everything else
+	if (zero) { return z; }
+	Real r = pow(mag_sq(z), power / 2.0);
+	Real theta = atan2(z.imag, z.real) * power;
+	return { r * cos(theta), r * sin(theta) };
+}
+
+__device__ Complex f(Complex z, Complex c, Real power, uint32_t fractal) {
+	// Some fractals fold z before raising it to the power
+	if (fractal == FRACTAL_BURNING_SHIP) {
+		z = { fabs(z.real), fabs(z.imag) };
+	} else if (fractal == FRACTAL_TRICORN) {
+		z.imag = -z.imag;
+	}
+
+	Complex p;
+	if (power == 2.0) {
+		Real w = mul(z.real, z.imag);
+		p = { sub(mul(z.real, z.real), mul(z.imag, z.imag)), add(w, w) };
+	} else {
+		p = cpow(z, power);
+	}
+
+	// And others fold it afterwards
+	if (fractal == FRACTAL_CELTIC || fractal == FRACTAL_BUFFALO) {
+		p.real = fabs(p.real);
+	}
+	if (fractal == FRACTAL_BUFFALO) {
+		p.imag = fabs(p.imag);
+	}
 
-	return { real, imag };
+	return { add(p.real, c.real), add(p.imag, c.imag) };
+}
+
+// Derivative of z^power at z
+__device__ Complex slope(Complex z, Real power) {
+	if (power == 2.0) {
+		return { add(z.real, z.real), add(z.imag, z.imag) };
+	}
+
+	Complex p = cpow(z, power - 1.0);
+	return { mul(p.real, power), mul(p.imag, power) };
+}
+
+// Whether z has escaped past the radius, measured as the bailout says
+__device__ bool escaped(Complex z, Real radius, uint32_t bailout) {
+	switch (bailout) {
+	case BAILOUT_REAL: return fabs(z.real) > radius;
+	case BAILOUT_IMAGINARY: return fabs(z.imag) > radius;
+	case BAILOUT_MANHATTAN: return add(fabs(z.real), fabs(z.imag)) > radius;
+	default: return mag_sq(z) > mul(radius, radius);
+	}
 }
 
 __global__ void compute(Pixel *pixels, 
 						unsigned long width, 
 						unsigned long height, 
 						unsigned long iterations, 
+						Real power,
+						uint32_t fractal,
+						bool julia,
+						Real tolerance,
+						Real escape_radius,
+						uint32_t bailout,
+						bool stats,
+						uint32_t stride,
 						unsigned long long *progress) {
 
 	// Figure out which pixel this thread is responsible for
@@ -33,14 +116,51 @@ __global__ void compute(Pixel *pixels,
 			// Get a pointer to our pixel
 			Pixel *pixel = &pixels[idx];
 
-			// Loop until the pixel diverges, or the max iterations is reached
-			while (pixel->i < iterations && !pixel->d) {
-				pixel->z = f(pixel->z, pixel->c);
+			// Periodic orbits are caught by comparing z against a checkpoint that moves up to the
+			// latest z after 1, 2, 4, 8, ... iterations (Brent's method)
+			Complex checkpoint = pixel->z;
+			uint32_t since = 0, gap = 1;
+
+			// Pixels off the stride are left for a later pass
+			bool on_stride = x % stride == 0 && y % stride == 0;
+
+			// Loop until the pixel diverges, is found to be periodic, or the max iterations is reached
+			while (on_stride && pixel->i < iterations && !pixel->d) {
+				// The derivative goes p z^(p-1) dz + 1, without the 1 for the Julia set.  The fold is
+				// left out, so it's only an estimate for the folded fractals.
+				pixel->dz = cmul(slope(pixel->z, power), pixel->dz);
+				if (!julia) { pixel->dz.real = add(pixel->dz.real, 1.0); }
+
+				pixel->z = f(pixel->z, pixel->c, power, fractal);
 				pixel->i += 1;
+				since += 1;
+
+				// Check to see if it's diverged or come back around
+				Complex offset = { sub(pixel->z.real, checkpoint.real), sub(pixel->z.imag, checkpoint.imag) };
+				if (stats && sqrt(mag_sq(pixel->z)) < pixel->min_abs) {
+					pixel->min_abs = sqrt(mag_sq(pixel->z));
+					pixel->min_i = pixel->i;
+				}
 
-				// Check to see if it's diverged
-				if (mag_sq(pixel->z) > 4.0) {
+				if (escaped(pixel->z, escape_radius, bailout)) {
 					pixel->d = true;
+				} else if (mag_sq(offset) <= mul(tolerance, tolerance)) {
+					pixel->period = since;
+					pixel->i = iterations;
+
+					// The multiplier comes from going around the cycle once more
+					if (stats) {
+						Complex point = pixel->z, multiplier = { 1.0, 0.0 };
+						for (uint32_t k = 0; k < since; k++) {
+							multiplier = cmul(multiplier, slope(point, power));
+							point = f(point, pixel->c, power, fractal);
+						}
+						pixel->multiplier = sqrt(mag_sq(multiplier));
+					}
+				} else if (since == gap) {
+					checkpoint = pixel->z;
+					since = 0;
+					gap *= 2;
 				}
 			}
 
@@ -51,7 +171,15 @@ __global__ void compute(Pixel *pixels,
 }
 
 extern "C" {
-	uint32_t cuda_compute(uint32_t iterations, RenderData data, void **progress) {
+	int32_t cuda_device_count() {
+		// Any error (no driver, no device, etc.) means there's nothing to run on
+		int count = 0;
+		if (cudaGetDeviceCount(&count) != cudaSuccess) { return 0; }
+
+		return count;
+	}
+
+	uint32_t cuda_compute(uint32_t iterations, Real power, uint32_t fractal, bool julia, Real tolerance, Real escape_radius, uint32_t bailout, bool stats, uint32_t stride, RenderData data, void **progress) {
 		// Make sure the image isn't too big
 		if (data.width > 2097120 || data.height > 2097120) {
 			// Too big :( TODO: not really, we can go quite a bit bigger, but we'll do that later
@@ -92,7 +220,7 @@ extern "C" {
 		if (data.height % blockSize.y) { gridSize.y += 1; }
 
 		// Run kernel
-		compute<<<gridSize, blockSize>>>(pixels, data.width, data.height, iterations, progress_shared);
+		compute<<<gridSize, blockSize>>>(pixels, data.width, data.height, iterations, power, fractal, julia, tolerance, escape_radius, bailout, stats, stride, progress_shared);
 		status = cudaPeekAtLastError();
 
 		if (status != cudaSuccess) { return status; }
@@ -2,6 +2,20 @@
 
 typedef double Real;
 
+// Fractals that the kernel can fold z for.  The Mandelbrot and Julia sets are both 0, since they only
+// differ in their starting values.
+#define FRACTAL_MANDELBROT 0
+#define FRACTAL_BURNING_SHIP 1
+#define FRACTAL_TRICORN 2
+#define FRACTAL_CELTIC 3
+#define FRACTAL_BUFFALO 4
+
+// How z is measured against the escape radius
+#define BAILOUT_NORM 0
+#define BAILOUT_REAL 1
+#define BAILOUT_IMAGINARY 2
+#define BAILOUT_MANHATTAN 3
+
 typedef struct {
 	Real real;
 	Real imag;
@@ -12,6 +26,15 @@ typedef struct {
 	Complex z;
 	uint32_t i;
 	bool d;
+	// Period of the orbit's cycle, or 0 if it hasn't been found to be periodic
+	uint32_t period;
+	// Derivative of z with respect to c (or the starting z, for the Julia set)
+	Complex dz;
+	// Smallest |z| so far and the iteration it was reached at (0 if there isn't one yet), and the
+	// size of the cycle's multiplier once it's found to be periodic
+	Real min_abs;
+	uint32_t min_i;
+	Real multiplier;
 } Pixel;
 
 typedef struct {
@@ -23,5 +46,6 @@ typedef struct {
 } RenderData;
 
 extern "C" {
-	uint32_t cuda_compute(uint32_t iterations, RenderData data, void **progress);
+	int32_t cuda_device_count();
+	uint32_t cuda_compute(uint32_t iterations, Real power, uint32_t fractal, bool julia, Real tolerance, Real escape_radius, uint32_t bailout, bool stats, uint32_t stride, RenderData data, void **progress);
 }
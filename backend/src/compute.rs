@@ -0,0 +1,127 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+
+use crate::cpu::CpuBackend;
+#[cfg(feature = "cuda")]
+use crate::cuda::CudaBackend;
+use crate::perturb::PerturbationBackend;
+use crate::render::{Parameters, Render, RenderError};
+
+pub type Result = std::result::Result<Render, RenderError>;
+
+// Something that can iterate the pixels of a Render.  Implementations pick up each pixel from
+// wherever it was left off, and stop once it diverges or reaches the render's max_iter.
+pub trait ComputeBackend: Send {
+    // Name of the backend, as shown to the user
+    fn name(&self) -> &'static str;
+
+    // Whether the backend can run on this machine
+    fn available(&self) -> bool;
+
+    // Whether the backend can render with the given parameters
+    fn supports(&self, _params: &Parameters) -> bool {
+        true
+    }
+
+    // Iterate the render, keeping `progress` updated with the percentage of pixels completed.
+    // Backends should check `cancel` as often as is practical, and return an error as soon as
+    // they notice it has been set.
+    fn compute(
+        &self,
+        render: Render,
+        progress: Arc<Mutex<Option<f64>>>,
+        cancel: Arc<AtomicBool>,
+    ) -> Result;
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+// The user's choice of backend
+pub enum Backend {
+    Auto,
+    Cpu,
+    Cuda,
+    Perturbation,
+}
+
+impl Backend {
+    // Get the implementation for this choice, to render with the given parameters.  Auto prefers
+    // CUDA, and falls back to the CPU, using perturbation when f64s aren't precise enough.
+    pub fn get(
+        self,
+        params: &Parameters,
+    ) -> std::result::Result<Box<dyn ComputeBackend>, RenderError> {
+        let backend: Box<dyn ComputeBackend> = match self {
+            Backend::Auto => match cuda() {
+                Some(cuda) if cuda.available() && cuda.supports(params) => cuda,
+                _ if params.precision() > 53 && PerturbationBackend.supports(params) => {
+                    Box::new(PerturbationBackend)
+                }
+                _ => Box::new(CpuBackend),
+            },
+            Backend::Cpu => Box::new(CpuBackend),
+            Backend::Cuda => match cuda() {
+                Some(cuda) => cuda,
+                None => {
+                    return Err(RenderError(
+                        "This build doesn't include CUDA support (rebuild with --features cuda)."
+                            .into(),
+                    ))
+                }
+            },
+            Backend::Perturbation => Box::new(PerturbationBackend),
+        };
+
+        if !backend.available() {
+            Err(RenderError(format!(
+                "The {} backend is not available.",
+                backend.name()
+            )))
+        } else if !backend.supports(params) {
+            Err(RenderError(format!(
+                "The {} backend can't render with these parameters.",
+                backend.name()
+            )))
+        } else {
+            Ok(backend)
+        }
+    }
+}
+
+// The CUDA backend, if this build includes it
+#[cfg(feature = "cuda")]
+fn cuda() -> Option<Box<dyn ComputeBackend>> {
+    Some(Box::new(CudaBackend))
+}
+
+#[cfg(not(feature = "cuda"))]
+fn cuda() -> Option<Box<dyn ComputeBackend>> {
+    None
+}
+
+// Allow for parsing backends from user input
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim() {
+            "auto" => Ok(Backend::Auto),
+            "cpu" => Ok(Backend::Cpu),
+            "cuda" => Ok(Backend::Cuda),
+            "perturbation" => Ok(Backend::Perturbation),
+            s => Err(format!("No such backend: {}.", s)),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Backend::Auto => write!(f, "auto"),
+            Backend::Cpu => write!(f, "cpu"),
+            Backend::Cuda => write!(f, "cuda"),
+            Backend::Perturbation => write!(f, "perturbation"),
+        }
+    }
+}
@@ -0,0 +1,800 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use crate::buddhabrot;
+use crate::compute::{ComputeBackend, Result};
+use crate::lyapunov::Sequence;
+use crate::math::{is_integer, Complex, DoubleDouble, Float, Real};
+use crate::newton::Polynomial;
+use crate::render::{
+    next_derivative, slope, Bailout, FractalKind, Parameters, PixelDetails, Render, RenderError,
+};
+use crate::threadpool::ThreadPool;
+use crate::trap::Trap;
+
+// Windows that need up to this many bits of precision are iterated with DoubleDoubles rather than
+// Reals
+const DOUBLE_DOUBLE_PRECISION: u32 = 106;
+
+// Size of the tiles that the image is cut into for subdivision, and the size below which a
+// rectangle that isn't all the same is iterated pixel by pixel rather than split any further
+const TILE_SIZE: u32 = 64;
+const MIN_SUBDIVISION: u32 = 4;
+
+// Iterations that the Lyapunov fractal's logistic map is given to settle down before its exponent is
+// measured
+const LYAPUNOV_WARMUP: u32 = 50;
+
+// A pixel, along with its details
+type Item = ((u32, Complex, Complex, bool), PixelDetails);
+
+// Part of the image, as the position of its top left pixel and its width and height
+pub type Tile = (u32, u32, u32, u32);
+
+#[derive(Clone)]
+// When to stop iterating a pixel that hasn't diverged: once it reaches the max iterations, or once
+// its orbit comes back to within `tolerance` of an earlier point, since then it's periodic.  Along
+// the way, the derivative of z is tracked for the given power if there is one, and with respect to
+// the starting z if it's for the Julia set.  z has diverged once it escapes past the given radius.
+// If `stats` is set, pixels that don't diverge also keep track of their interior stats, and if
+// there's a trap, every pixel keeps track of how close it's come to it.  If there's a stripe density,
+// every pixel keeps its averages too.
+struct Limits {
+    iterations: u32,
+    tolerance: f64,
+    derivative: Option<(f64, bool)>,
+    escape: (Bailout, f64),
+    stats: bool,
+    trap: Option<Trap>,
+    averages: Option<f64>,
+}
+
+// Backend that splits the render up by rows across all of the CPU's cores
+pub struct CpuBackend;
+
+impl ComputeBackend for CpuBackend {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn available(&self) -> bool {
+        true
+    }
+
+    // Only f64s have the functions needed for formulas, the Newton fractal and powers that aren't
+    // integers, and the Buddhabrot and Lyapunov fractal are only worked out with f64s
+    fn supports(&self, params: &Parameters) -> bool {
+        params.precision() <= 53
+            || (params.formula.is_none()
+                && params.fractal != FractalKind::Newton
+                && params.fractal != FractalKind::Lyapunov
+                && !params.fractal.density()
+                && is_integer(params.power))
+    }
+
+    fn compute(
+        &self,
+        render: Render,
+        progress: Arc<Mutex<Option<f64>>>,
+        cancel: Arc<AtomicBool>,
+    ) -> Result {
+        if render.params.fractal.density() {
+            buddhabrot::compute(render, progress, cancel)
+        } else {
+            compute(render, progress, cancel)
+        }
+    }
+}
+
+pub fn compute(
+    render: Render,
+    progress: Arc<Mutex<Option<f64>>>,
+    cancel: Arc<AtomicBool>,
+) -> Result {
+    let (width, height) = render.params.image_size;
+    let iterations = render.params.max_iter;
+    let power = render.params.power;
+    let fractal = render.params.fractal;
+    let limits = Limits {
+        iterations,
+        tolerance: render.params.period_tolerance(),
+        derivative: match render.params.formula {
+            Some(_) => None,
+            None => Some((power, fractal == FractalKind::Julia)),
+        },
+        escape: (render.params.bailout, render.params.escape_radius),
+        stats: render.params.interior_stats,
+        trap: render.params.trap.clone(),
+        averages: if render.params.averages {
+            Some(render.params.stripe_density)
+        } else {
+            None
+        },
+    };
+    let flipped = fractal.flipped();
+    let formula = render.params.formula.clone();
+    let polynomial = render.params.polynomial.clone();
+    let sequence = render.params.sequence.clone();
+
+    // Iterate with f64s if they're precise enough for this window, then DoubleDoubles, and only
+    // then Reals
+    let precision = render.params.precision();
+    let (center, radius) = (
+        render.params.center.with_precision(precision),
+        render.params.radius.with_precision(precision),
+    );
+
+    // The Julia set's constant c, if it's being rendered
+    let julia = render
+        .params
+        .julia_constant()
+        .map(|c| c.with_precision(precision));
+    let julia_dd = julia.as_ref().map(Complex::<DoubleDouble>::from_real);
+
+    let items: Vec<Item> = render.pixels.into_iter().zip(render.details).collect();
+
+    // With subdivision the image is cut into square tiles, and otherwise each row is its own tile.
+    // Subdivision would fill in the pixels between the ones on the stride, so it's only used when
+    // every pixel is iterated.  Interior stats, orbit traps and averages are different for every
+    // pixel's orbit, so they can't be filled in either.
+    let stride = render.stride;
+    let subdivision = render.params.subdivision
+        && stride == 1
+        && !limits.stats
+        && limits.trap.is_none()
+        && limits.averages.is_none();
+    let size = if subdivision {
+        (TILE_SIZE, TILE_SIZE)
+    } else {
+        (width, 1)
+    };
+
+    let cancelled = Arc::clone(&cancel);
+    let items = for_each_tile(items, width, size, Some(&progress), move |tile, items| {
+        if precision <= 53 {
+            run_tile(tile, items, (subdivision, stride), &cancel, |_, _, item| {
+                let ((i, c, z, d), details) = item;
+
+                if let Some(formula) = &formula {
+                    iterate(i, c, z, d, details, &limits, |z, c| {
+                        formula.evaluate(*z, *c)
+                    });
+                } else if fractal == FractalKind::Newton {
+                    newton(i, z, d, details, &polynomial, iterations);
+                } else if fractal == FractalKind::Lyapunov {
+                    lyapunov(i, c, &mut z.0, d, details, &sequence, iterations);
+                } else if is_integer(power) {
+                    iterate(i, c, z, d, details, &limits, |z, c| {
+                        fractal.pow(z, |z| z.powi(power as i32)) + *c
+                    });
+                } else {
+                    iterate(i, c, z, d, details, &limits, |z, c| {
+                        fractal.pow(z, |z| z.powf(power)) + *c
+                    });
+                }
+            });
+        } else if precision <= DOUBLE_DOUBLE_PRECISION {
+            let mapping =
+                Complex::<DoubleDouble>::get_mapping((width, height), (&center, &radius), flipped);
+            let step = |z: &Complex<DoubleDouble>, c: &Complex<DoubleDouble>| {
+                fractal.pow(z, |z| z.powi(power as i32)) + *c
+            };
+            run_tile(tile, items, (subdivision, stride), &cancel, |x, y, item| {
+                restart_pixel(item, (x, y), &mapping, julia_dd.as_ref(), &step, &limits)
+            });
+        } else {
+            let mapping =
+                Complex::<Real>::get_mapping((width, height), (&center, &radius), flipped);
+            let step = |z: &Complex<Real>, c: &Complex<Real>| {
+                fractal.pow(z, |z| z.powi(power as i32)) + c.clone()
+            };
+            run_tile(tile, items, (subdivision, stride), &cancel, |x, y, item| {
+                restart_pixel(item, (x, y), &mapping, julia.as_ref(), &step, &limits)
+            });
+        }
+    })?;
+
+    if cancelled.load(Ordering::Relaxed) {
+        return Err(RenderError("Render cancelled.".into()));
+    }
+
+    let (pixels, details) = items.into_iter().unzip();
+
+    Ok(Render {
+        iterations,
+        pixels,
+        details,
+        ..render
+    })
+}
+
+// Run `f` on every row of an image's worth of items, spread across all of the CPU's cores.  `f` is
+// given the row number and the row's items.  If `progress` is given, it's kept updated with the
+// percentage of items whose row has finished.
+pub fn for_each_row<T, F>(
+    items: Vec<T>,
+    width: u32,
+    progress: Option<&Mutex<Option<f64>>>,
+    f: F,
+) -> std::result::Result<Vec<T>, RenderError>
+where
+    T: Clone + Send + 'static,
+    F: Fn(usize, &mut [T]) + Send + Sync + 'static,
+{
+    for_each_tile(
+        items,
+        width,
+        (width, 1),
+        progress,
+        move |(_, y, _, _), row| f(y as usize, row),
+    )
+}
+
+// Run `f` on every tile of an image's worth of items, spread across all of the CPU's cores.  The
+// image is cut into tiles of the given size (or smaller, at the right and bottom edges), and `f` is
+// given each tile along with its items, row by row.  If `progress` is given, it's kept updated with
+// the percentage of items whose tile has finished.
+pub fn for_each_tile<T, F>(
+    items: Vec<T>,
+    width: u32,
+    size: (u32, u32),
+    progress: Option<&Mutex<Option<f64>>>,
+    f: F,
+) -> std::result::Result<Vec<T>, RenderError>
+where
+    T: Clone + Send + 'static,
+    F: Fn(Tile, &mut [T]) + Send + Sync + 'static,
+{
+    let height = items.len() as u32 / width;
+    let max = items.len();
+
+    // Share the items between the workers, each of which will take one tile at a time
+    let items = Arc::new(Mutex::new(items));
+    let f = Arc::new(f);
+
+    // Progress counter, in number of items
+    let done = Arc::new(AtomicUsize::new(0));
+
+    // Each worker sends on this channel when it has finished a tile
+    let (tile_sender, tile_receiver) = mpsc::channel();
+
+    // Use one worker per core
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let pool = ThreadPool::new(threads, tile_sender);
+
+    let mut tiles = 0;
+    for y in (0..height).step_by(size.1 as usize) {
+        for x in (0..width).step_by(size.0 as usize) {
+            let items = Arc::clone(&items);
+            let done = Arc::clone(&done);
+            let f = Arc::clone(&f);
+            let tile = (
+                x,
+                y,
+                std::cmp::min(size.0, width - x),
+                std::cmp::min(size.1, height - y),
+            );
+            tiles += 1;
+
+            pool.execute(move |tile_sender| {
+                let (x, y, w, h) = tile;
+                let rows = (y..y + h).map(|row| (row * width + x) as usize);
+
+                // Copy the tile out, so the other workers aren't blocked while we work on it
+                let mut tile_items: Vec<T> = Vec::with_capacity((w * h) as usize);
+                {
+                    let items = items.lock().unwrap();
+                    for start in rows.clone() {
+                        tile_items.extend_from_slice(&items[start..start + w as usize]);
+                    }
+                }
+
+                f(tile, &mut tile_items);
+
+                // Put the tile back and report that it's done
+                {
+                    let mut items = items.lock().unwrap();
+                    for (start, row) in rows.zip(tile_items.chunks(w as usize)) {
+                        items[start..start + w as usize].clone_from_slice(row);
+                    }
+                }
+                done.fetch_add(tile_items.len(), Ordering::Relaxed);
+                tile_sender.send(()).unwrap();
+            });
+        }
+    }
+
+    // Wait for every tile to finish, updating the progress in the meantime
+    let mut finished = 0;
+    while finished < tiles {
+        if tile_receiver
+            .recv_timeout(Duration::from_millis(10))
+            .is_ok()
+        {
+            finished += 1;
+        }
+
+        if let Some(progress) = progress {
+            let p = done.load(Ordering::Relaxed) as f64 / max as f64 * 100.0;
+            *progress.lock().unwrap() = Some(p);
+        }
+    }
+
+    // Shut down the workers, which releases their references to the items
+    drop(pool);
+
+    match Arc::try_unwrap(items) {
+        Ok(items) => Ok(items.into_inner().unwrap()),
+        Err(_) => Err(RenderError("Worker threads did not finish.".into())),
+    }
+}
+
+// Iterate a tile's pixels with `pixel`, which is given each pixel's position in the image.  Only
+// the pixels on every stride-th column and row of the image are iterated.  With subdivision, as
+// much of the tile as possible is filled in without being iterated.
+fn run_tile<P: FnMut(u32, u32, &mut Item)>(
+    tile: Tile,
+    items: &mut [Item],
+    (subdivision, stride): (bool, u32),
+    cancel: &AtomicBool,
+    mut pixel: P,
+) {
+    let (x, y, w, h) = tile;
+
+    if subdivision {
+        subdivide(tile, (0, 0, w - 1, h - 1), items, cancel, &mut pixel);
+    } else {
+        for (idx, item) in items.iter_mut().enumerate() {
+            // Leave the rest of the tile alone if the render has been cancelled
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let (column, row) = (x + idx as u32 % w, y + idx as u32 / w);
+            if column.is_multiple_of(stride) && row.is_multiple_of(stride) {
+                pixel(column, row, item);
+            }
+        }
+    }
+}
+
+// Fill in a rectangle of a tile with the Mariani-Silver algorithm, given the rectangle's first and
+// last columns and rows within the tile.  The pixels around its edge are iterated first, and if
+// none of them diverged and they all came out the same, everything inside is assumed to be the
+// same too, and is filled in from the edge and marked as guessed.  That holds for connected sets
+// like the Mandelbrot set, but is only a heuristic for the rest, which can have a piece of the
+// outside entirely within the rectangle.  Escaped pixels are never filled in, since their z and
+// derivative differ from pixel to pixel.  Otherwise the rectangle is split into quarters, which
+// share their edges, until they're too small to be worth splitting.
+fn subdivide<P: FnMut(u32, u32, &mut Item)>(
+    tile: Tile,
+    (left, top, right, bottom): (u32, u32, u32, u32),
+    items: &mut [Item],
+    cancel: &AtomicBool,
+    pixel: &mut P,
+) {
+    let (x, y, w, _) = tile;
+    let idx = |column: u32, row: u32| (column + row * w) as usize;
+
+    // Leave the rest of the tile alone if the render has been cancelled
+    if cancel.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let edge: Vec<(u32, u32)> = (left..=right)
+        .flat_map(|column| [(column, top), (column, bottom)])
+        .chain((top..=bottom).flat_map(|row| [(left, row), (right, row)]))
+        .collect();
+    for &(column, row) in &edge {
+        pixel(x + column, y + row, &mut items[idx(column, row)]);
+    }
+
+    // Nothing left inside
+    if right - left < 2 || bottom - top < 2 {
+        return;
+    }
+
+    // Pixels come out the same if they have the same iterations, root, exponent and period, and
+    // either both diverged or neither did.  Their derivatives are bound to be different, like their
+    // z, and nothing else is recorded when subdividing.
+    let outcome = |((i, _, _, d), details): &Item| {
+        (
+            *i,
+            *d,
+            details.root,
+            details.exponent,
+            details.interior,
+            details.period,
+        )
+    };
+    let first = items[idx(left, top)];
+    let inside =
+        (top + 1..bottom).flat_map(|row| (left + 1..right).map(move |column| (column, row)));
+
+    let ((_, _, _, diverged), _) = first;
+
+    if !diverged
+        && edge
+            .iter()
+            .all(|&(column, row)| outcome(&items[idx(column, row)]) == outcome(&first))
+    {
+        // Filled in pixels keep their own starting z and derivative rather than the corner's, so
+        // that they can be picked up from again if there are more iterations later
+        let ((i, _, _, _), details) = first;
+        for (column, row) in inside {
+            let ((pixel_i, _, _, _), pixel_details) = &mut items[idx(column, row)];
+            *pixel_i = i;
+            *pixel_details = PixelDetails {
+                root: details.root,
+                exponent: details.exponent,
+                interior: details.interior,
+                period: details.period,
+                guessed: true,
+                ..*pixel_details
+            };
+        }
+    } else if right - left < MIN_SUBDIVISION || bottom - top < MIN_SUBDIVISION {
+        for (column, row) in inside {
+            pixel(x + column, y + row, &mut items[idx(column, row)]);
+        }
+    } else {
+        let (middle, centre) = ((left + right) / 2, (top + bottom) / 2);
+        subdivide(tile, (left, top, middle, centre), items, cancel, pixel);
+        subdivide(tile, (middle, top, right, centre), items, cancel, pixel);
+        subdivide(tile, (left, centre, middle, bottom), items, cancel, pixel);
+        subdivide(tile, (middle, centre, right, bottom), items, cancel, pixel);
+    }
+}
+
+// Iterate a pixel with a more precise type than f64, given its position in the image.  Only the f64
+// approximation of z is stored, which isn't precise enough to pick up from, so if the pixel hasn't
+// diverged or reached the max iterations it starts again from the beginning.  If the Julia set's
+// constant is given, the mapping gives the pixel's starting z rather than its c.  `step` gives the
+// next z from z and c, and can't use a formula or a power that isn't an integer.
+fn restart_pixel<T: Float, S: Fn(&Complex<T>, &Complex<T>) -> Complex<T>>(
+    ((i, _, z, d), details): &mut Item,
+    (x, y): (u32, u32),
+    mapping: &dyn Fn(u32, u32) -> Complex<T>,
+    julia: Option<&Complex<T>>,
+    step: &S,
+    limits: &Limits,
+) {
+    if !*d && *i < limits.iterations {
+        let (c, mut precise_z) = match julia {
+            Some(c) => (c.clone(), mapping(x, y)),
+            None => (mapping(x, y), Complex(T::from_f64(0.0), T::from_f64(0.0))),
+        };
+        *i = 0;
+        details.derivative = match julia {
+            Some(_) => Complex(1.0, 0.0),
+            None => Complex(0.0, 0.0),
+        };
+        details.minimum = None;
+        details.trap = None;
+        details.stripe = None;
+        details.triangle = None;
+
+        iterate(i, &c, &mut precise_z, d, details, limits, step);
+        *z = precise_z.to_f64();
+    }
+}
+
+// Iterate a single pixel until it diverges or reaches the given limits, picking up from wherever it
+// was left off.  `step` gives the next z from z and c, whether that's with the user's formula or the
+// fractal's own.
+//
+// Periodic orbits are caught with Brent's method, comparing z against a checkpoint that moves up to
+// the latest z after 1, 2, 4, 8, ... iterations, so that any cycle is found once the gap between
+// checkpoints reaches its period.  A periodic pixel is inside the set, so it's given the max
+// iterations, and its details record the period.
+//
+// With interior stats, the smallest |z| is kept until the pixel diverges, and once a cycle is found
+// its multiplier comes from going around it once more, multiplying together the slope at each point.
+fn iterate<T: Float, F: Fn(&Complex<T>, &Complex<T>) -> Complex<T>>(
+    i: &mut u32,
+    c: &Complex<T>,
+    z: &mut Complex<T>,
+    d: &mut bool,
+    details: &mut PixelDetails,
+    limits: &Limits,
+    step: F,
+) {
+    let tolerance = limits.tolerance * limits.tolerance;
+    let (mut checkpoint, mut since, mut gap) = (z.clone(), 0, 1);
+
+    while *i < limits.iterations && !*d {
+        if let Some((power, julia)) = limits.derivative {
+            details.derivative = next_derivative(z.to_f64(), details.derivative, power, julia);
+        }
+
+        let previous = z.to_f64();
+        *z = step(z, c);
+        *i += 1;
+        since += 1;
+
+        // Check to see if it's diverged or come back around
+        let escaped = limits.escape.0.escaped(z, limits.escape.1);
+        if limits.stats {
+            details.record_minimum(z.abs_squared().to_f64().sqrt(), *i, escaped);
+        }
+        if let Some(trap) = &limits.trap {
+            details.record_trap(trap, z.to_f64());
+        }
+        if let Some(density) = limits.averages {
+            let power = limits.derivative.map(|(power, _)| power);
+            let step = (previous, z.to_f64(), c.to_f64());
+            details.record_averages(step, *i, power, density);
+        }
+
+        if escaped {
+            *d = true;
+        } else if (z.clone() - checkpoint.clone()).abs_squared().to_f64() <= tolerance {
+            details.period = Some(since);
+            details.interior = true;
+            *i = limits.iterations;
+
+            if let (true, Some((power, _))) = (limits.stats, limits.derivative) {
+                let mut point = z.clone();
+                let mut multiplier = Complex(1.0, 0.0);
+                for _ in 0..since {
+                    multiplier = multiplier * slope(point.to_f64(), power);
+                    point = step(&point, c);
+                }
+                details.multiplier = Some(multiplier.abs());
+            }
+        } else if since == gap {
+            checkpoint = z.clone();
+            since = 0;
+            gap *= 2;
+        }
+    }
+}
+
+// Run Newton's method on a single pixel until it reaches one of the polynomial's roots or the given
+// number of iterations, picking up from wherever it was left off.  The pixel counts as finished
+// (rather than diverged) once it reaches a root.
+fn newton(
+    i: &mut u32,
+    z: &mut Complex,
+    d: &mut bool,
+    details: &mut PixelDetails,
+    polynomial: &Polynomial,
+    iterations: u32,
+) {
+    while !*d {
+        details.root = polynomial.root_near(*z);
+
+        if details.root.is_some() {
+            *d = true;
+        } else if *i < iterations {
+            match polynomial.step(*z) {
+                Some(next) => {
+                    *z = next;
+                    *i += 1;
+                }
+                // Stuck where p'(z) is zero, which never reaches a root
+                None => *i = iterations,
+            }
+        } else {
+            break;
+        }
+    }
+}
+
+// Run the Lyapunov fractal's logistic map for a single pixel until it reaches the given number of
+// iterations, picking up from wherever it was left off.  The exponent is the average of
+// ln|r (1 - 2x)| once the map has settled down.  The pixel counts as diverged if x runs off to
+// infinity, which it can when r isn't between 0 and 4.
+fn lyapunov(
+    i: &mut u32,
+    c: &Complex,
+    x: &mut f64,
+    d: &mut bool,
+    details: &mut PixelDetails,
+    sequence: &Sequence,
+    iterations: u32,
+) {
+    let measured = |i: u32| i.saturating_sub(LYAPUNOV_WARMUP) as f64;
+    let mut sum = details.exponent * measured(*i);
+
+    while *i < iterations && !*d {
+        let r = sequence.rate(*i, c.0, c.1);
+        if *i >= LYAPUNOV_WARMUP {
+            sum += (r * (1.0 - 2.0 * *x)).abs().ln();
+        }
+
+        let next = r * *x * (1.0 - *x);
+        *i += 1;
+        if next.is_finite() {
+            *x = next;
+        } else {
+            *d = true;
+        }
+    }
+
+    if measured(*i) > 0.0 {
+        details.exponent = sum / measured(*i);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Parameters;
+
+    // Render the window all the way through on the CPU
+    fn render(params: Parameters) -> Render {
+        let progress = Arc::new(Mutex::new(None));
+        let cancel = Arc::new(AtomicBool::new(false));
+        compute(Render::new(params), progress, cancel).unwrap()
+    }
+
+    fn mandelbrot(subdivision: bool) -> Parameters {
+        Parameters {
+            image_size: (96, 80),
+            center: Complex("-0.5".parse().unwrap(), "0".parse().unwrap()),
+            radius: "1.25".parse().unwrap(),
+            max_iter: 200,
+            subdivision,
+            ..Parameters::default()
+        }
+    }
+
+    #[test]
+    fn subdivision_matches_a_full_render() {
+        let full = render(mandelbrot(false));
+        let subdivided = render(mandelbrot(true));
+        assert_eq!(full.guessed(), 0.0);
+
+        let mut guessed = 0;
+        for idx in 0..full.pixels.len() {
+            let (pixel, details) = (subdivided.pixels[idx], subdivided.details[idx]);
+            if details.guessed {
+                // Only the inside of the set is filled in, which the full render agrees with
+                guessed += 1;
+                assert!(!pixel.3 && pixel.0 == 200);
+                assert_eq!(pixel.0, full.pixels[idx].0);
+                assert_eq!(pixel.3, full.pixels[idx].3);
+            } else {
+                assert_eq!(pixel, full.pixels[idx]);
+                assert_eq!(details, full.details[idx]);
+            }
+        }
+
+        assert!(guessed > 0);
+        assert_eq!(
+            subdivided.guessed(),
+            guessed as f64 / full.pixels.len() as f64
+        );
+    }
+
+    #[test]
+    fn subdivision_is_skipped_for_per_pixel_records() {
+        let stats = render(Parameters {
+            interior_stats: true,
+            ..mandelbrot(true)
+        });
+        assert_eq!(stats.guessed(), 0.0);
+
+        let averages = render(Parameters {
+            averages: true,
+            ..mandelbrot(true)
+        });
+        assert_eq!(averages.guessed(), 0.0);
+    }
+
+    fn limits(bailout: Bailout) -> Limits {
+        Limits {
+            iterations: 50,
+            tolerance: 1e-10,
+            derivative: Some((2.0, false)),
+            escape: (bailout, 2.0),
+            stats: false,
+            trap: None,
+            averages: None,
+        }
+    }
+
+    #[test]
+    fn each_bailout_measures_z_its_own_way() {
+        // z goes -1.5+i (|z| 1.8), -0.25-2i (|z| 2.02), -5.4375+2i, then its imaginary part grows
+        let c = Complex(-1.5, 1.0);
+        for &(bailout, escape) in &[
+            (Bailout::Norm, 2),
+            (Bailout::Real, 3),
+            (Bailout::Imaginary, 4),
+            (Bailout::Manhattan, 1),
+        ] {
+            let (mut i, mut z, mut d) = (0, Complex(0.0, 0.0), false);
+            let mut details = PixelDetails::default();
+            iterate(
+                &mut i,
+                &c,
+                &mut z,
+                &mut d,
+                &mut details,
+                &limits(bailout),
+                |z, c| *z * *z + *c,
+            );
+            assert!(d, "{}", bailout);
+            assert_eq!(i, escape, "{}", bailout);
+        }
+    }
+
+    #[test]
+    fn newton_converges_to_the_nearest_root() {
+        // Every pixel in a small window around each root of z^3 - 1 ends up at that root
+        let roots = Polynomial::default();
+        for (idx, root) in roots.roots().iter().enumerate() {
+            let render = render(Parameters {
+                image_size: (8, 8),
+                center: Complex(Real::from(root.0), Real::from(root.1)),
+                radius: Real::from(0.1),
+                fractal: FractalKind::Newton,
+                ..Parameters::default()
+            });
+            for (pixel, details) in render.pixels.iter().zip(&render.details) {
+                assert!(pixel.3 && pixel.0 < 10);
+                assert_eq!(details.root, Some(idx as u32));
+            }
+        }
+
+        // z = 0 is a critical point of z^2 - 1, which Newton's method can't go anywhere from
+        let (mut i, mut z, mut d) = (0, Complex(0.0, 0.0), false);
+        let mut details = PixelDetails::default();
+        let polynomial = "1;-1".parse().unwrap();
+        newton(&mut i, &mut z, &mut d, &mut details, &polynomial, 100);
+        assert_eq!(
+            (i, z, d, details.root),
+            (100, Complex(0.0, 0.0), false, None)
+        );
+    }
+
+    // Run the logistic map for the given rates and sequence, from the start or from where `from`
+    // left off
+    fn logistic(
+        rates: (f64, f64),
+        sequence: &str,
+        iterations: u32,
+        from: Option<(u32, f64, PixelDetails)>,
+    ) -> (u32, f64, bool, PixelDetails) {
+        let (mut i, mut x, mut details) = from.unwrap_or((0, 0.5, PixelDetails::default()));
+        let mut d = false;
+        let sequence = sequence.parse().unwrap();
+        let c = Complex(rates.0, rates.1);
+        lyapunov(
+            &mut i,
+            &c,
+            &mut x,
+            &mut d,
+            &mut details,
+            &sequence,
+            iterations,
+        );
+        (i, x, d, details)
+    }
+
+    #[test]
+    fn lyapunov_exponents() {
+        // At r = 2.5, x settles on 1 - 1/r = 0.6, where the slope is r (1 - 2x) = -0.5
+        let (i, x, d, details) = logistic((2.5, 3.9), "A", 1000, None);
+        assert_eq!((i, d), (1000, false));
+        assert!((x - 0.6).abs() < 1e-12);
+        assert!((details.exponent - 0.5f64.ln()).abs() < 1e-3);
+
+        // r = 3.9 is chaotic, and r = 5 throws x out of [0, 1] and off to infinity
+        assert!(logistic((2.5, 3.9), "B", 1000, None).3.exponent > 0.3);
+        assert!(logistic((2.5, 3.9), "AB", 1000, None).3.exponent != 0.0);
+        let (i, _, d, _) = logistic((5.0, 3.9), "A", 1000, None);
+        assert!(d && i < 1000);
+
+        // Picking up where it left off gives the same exponent as going straight through
+        let (i, x, _, details) = logistic((2.5, 3.9), "AB", 500, None);
+        let resumed = logistic((2.5, 3.9), "AB", 1000, Some((i, x, details))).3;
+        let straight = logistic((2.5, 3.9), "AB", 1000, None).3;
+        assert!((resumed.exponent - straight.exponent).abs() < 1e-12);
+    }
+}
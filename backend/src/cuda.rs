@@ -1,15 +1,10 @@
-use std::error;
-use std::fmt;
 use std::io::{self, prelude::*};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+use crate::compute::{ComputeBackend, Result};
 use crate::math::Complex;
-use crate::render::Render;
-
-type Result = std::result::Result<Render, RenderError>;
-
-#[derive(Debug, Clone)]
-pub struct RenderError(pub String);
+use crate::render::{Bailout, FractalKind, Parameters, Render, RenderError};
 
 type FFIReal = f64;
 
@@ -25,6 +20,15 @@ struct FFIPixel {
     z: FFIComplex,
     i: u32,
     d: bool,
+    // Period of the orbit's cycle, or 0 if it hasn't been found to be periodic
+    period: u32,
+    // Derivative of z with respect to c (or the starting z, for the Julia set)
+    dz: FFIComplex,
+    // Smallest |z| so far and the iteration it was reached at (0 if there isn't one yet), and the
+    // size of the cycle's multiplier once it's found to be periodic
+    min_abs: FFIReal,
+    min_i: u32,
+    multiplier: FFIReal,
 }
 
 #[derive(Clone)]
@@ -38,7 +42,99 @@ pub struct FFIRenderData {
 }
 
 extern "C" {
-    fn cuda_compute(iterations: u32, data: FFIRenderData, progress: *mut *mut u64) -> u32;
+    fn cuda_compute(
+        iterations: u32,
+        power: f64,
+        fractal: u32,
+        julia: bool,
+        tolerance: f64,
+        escape_radius: f64,
+        bailout: u32,
+        stats: bool,
+        stride: u32,
+        data: FFIRenderData,
+        progress: *mut *mut u64,
+    ) -> u32;
+    fn cuda_device_count() -> i32;
+}
+
+// Backend that runs the computation on an Nvidia graphics card
+pub struct CudaBackend;
+
+impl ComputeBackend for CudaBackend {
+    fn name(&self) -> &'static str {
+        "cuda"
+    }
+
+    fn available(&self) -> bool {
+        available()
+    }
+
+    // The kernel only works with doubles, and doesn't know about formulas, orbit traps, averages,
+    // the Newton fractal, the Buddhabrot or the Lyapunov fractal
+    fn supports(&self, params: &Parameters) -> bool {
+        params.precision() <= 53
+            && params.formula.is_none()
+            && params.trap.is_none()
+            && !params.averages
+            && params.fractal != FractalKind::Newton
+            && params.fractal != FractalKind::Lyapunov
+            && !params.fractal.density()
+    }
+
+    fn compute(
+        &self,
+        render: Render,
+        progress: Arc<Mutex<Option<f64>>>,
+        cancel: Arc<AtomicBool>,
+    ) -> Result {
+        // The kernel can't be interrupted once it's launched, so the best we can do is check
+        // before starting and throw the result away afterwards
+        if cancel.load(Ordering::Relaxed) {
+            return Err(RenderError("Render cancelled.".into()));
+        }
+
+        let result = compute(render, progress);
+
+        if cancel.load(Ordering::Relaxed) {
+            Err(RenderError("Render cancelled.".into()))
+        } else {
+            result
+        }
+    }
+}
+
+// Check whether there is a CUDA-capable device that we can run the computation on
+pub fn available() -> bool {
+    unsafe { cuda_device_count() > 0 }
+}
+
+// Code for the kernel's FRACTAL_* constants in compute.cuh.  The Julia set doesn't need its own,
+// since its pixels already start from the right values, and the Newton fractal, Buddhabrot and
+// Lyapunov fractal aren't supported.
+fn fractal_code(fractal: FractalKind) -> u32 {
+    match fractal {
+        FractalKind::Mandelbrot
+        | FractalKind::Julia
+        | FractalKind::Newton
+        | FractalKind::Buddhabrot
+        | FractalKind::AntiBuddhabrot
+        | FractalKind::Lyapunov => 0,
+        FractalKind::BurningShip => 1,
+        FractalKind::Tricorn => 2,
+        FractalKind::Celtic => 3,
+        FractalKind::Buffalo => 4,
+    }
+}
+
+// Code for the kernel's BAILOUT_* constants in compute.cuh
+fn bailout_code(bailout: Bailout) -> u32 {
+    match bailout {
+        Bailout::Norm => 0,
+        Bailout::Real => 1,
+        Bailout::Imaginary => 2,
+        Bailout::Manhattan => 3,
+    }
 }
 
 pub fn compute(render: Render, progress_arc_mutex: Arc<Mutex<Option<f64>>>) -> Result {
@@ -46,11 +142,17 @@ pub fn compute(render: Render, progress_arc_mutex: Arc<Mutex<Option<f64>>>) -> R
     let mut pixels_vec: Vec<FFIPixel> = render
         .pixels
         .iter()
-        .map(|p| FFIPixel {
+        .zip(&render.details)
+        .map(|(p, details)| FFIPixel {
             c: FFIComplex::from(p.1),
             z: FFIComplex::from(p.2),
             i: p.0,
             d: p.3,
+            period: 0,
+            dz: FFIComplex::from(details.derivative),
+            min_abs: details.minimum.map_or(f64::INFINITY, |(size, _)| size),
+            min_i: details.minimum.map_or(0, |(_, i)| i),
+            multiplier: 0.0,
         })
         .collect();
 
@@ -65,7 +167,7 @@ pub fn compute(render: Render, progress_arc_mutex: Arc<Mutex<Option<f64>>>) -> R
     std::mem::forget(pixels_vec);
 
     // Progress counter
-    let mut progress: *mut u64 = 0 as *mut u64;
+    let mut progress: *mut u64 = std::ptr::null_mut();
     let max = render.params.image_size.0 * render.params.image_size.1;
 
     let progress_ptr: u64 = (&mut progress as *mut *mut u64) as u64;
@@ -103,6 +205,14 @@ pub fn compute(render: Render, progress_arc_mutex: Arc<Mutex<Option<f64>>>) -> R
     let result_code = unsafe {
         cuda_compute(
             render.params.max_iter,
+            render.params.power,
+            fractal_code(render.params.fractal),
+            render.params.fractal == FractalKind::Julia,
+            render.params.period_tolerance(),
+            render.params.escape_radius,
+            bailout_code(render.params.bailout),
+            render.params.interior_stats,
+            render.stride,
             data.clone(),
             &mut progress as *mut *mut u64,
         )
@@ -120,12 +230,33 @@ pub fn compute(render: Render, progress_arc_mutex: Arc<Mutex<Option<f64>>>) -> R
             let pixels_vec =
                 unsafe { Vec::from_raw_parts(data.pixels, data.num as usize, data.num as usize) };
 
+            // Periodic pixels are inside the set, and only pixels that didn't diverge keep their
+            // interior stats
+            let stats = render.params.interior_stats;
+            let mut details = render.details;
+            for (details, p) in details.iter_mut().zip(&pixels_vec) {
+                details.derivative = p.dz.to_complex();
+                if p.period > 0 {
+                    details.period = Some(p.period);
+                    details.interior = true;
+                    if stats {
+                        details.multiplier = Some(p.multiplier);
+                    }
+                }
+                details.minimum = if stats && !p.d && p.min_i > 0 {
+                    Some((p.min_abs, p.min_i))
+                } else {
+                    None
+                };
+            }
+
             let render = Render {
                 iterations: render.params.max_iter,
                 pixels: pixels_vec
                     .iter()
                     .map(|p| (p.i, p.c.to_complex(), p.z.to_complex(), p.d))
                     .collect(),
+                details,
                 ..render
             };
             Ok(render)
@@ -155,19 +286,3 @@ impl FFIComplex {
         Complex(self.real, self.imag)
     }
 }
-
-impl fmt::Display for RenderError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "RenderError: {}", self.0)
-    }
-}
-
-impl error::Error for RenderError {
-    fn description(&self) -> &str {
-        &self.0[..]
-    }
-
-    fn cause(&self) -> Option<&error::Error> {
-        None
-    }
-}
@@ -0,0 +1,334 @@
+use std::cmp::{self, Ordering};
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::FromStr;
+
+use crate::math::Float;
+use crate::real::Real;
+
+// Precision that strings are parsed at before being rounded, which is more than a DoubleDouble holds
+const PARSE_PRECISION: u32 = 128;
+
+// Most significant digits to try printing before falling back on the exact value
+const MAX_DIGITS: usize = 40;
+
+#[derive(Clone, Copy, Debug)]
+// Unevaluated sum of two f64s, giving about 106 bits of precision (with the exponent range of an
+// f64).  That covers moderate zooms at a fraction of the cost of a Real.
+pub struct DoubleDouble {
+    // The number rounded to an f64
+    hi: f64,
+    // What's left over, which is at most half of an ulp of hi
+    lo: f64,
+}
+
+impl DoubleDouble {
+    // Convert to a Real without losing anything
+    pub fn to_real(&self) -> Real {
+        if self.lo == 0.0 {
+            return Real::from(self.hi);
+        }
+
+        // Wide enough to cover every bit from the top of hi to the bottom of lo
+        let span = Real::from(self.hi).exponent() - Real::from(self.lo).exponent();
+        let precision = cmp::max(span + 53, 64) as u32;
+        Real::from(self.hi).with_precision(precision) + Real::from(self.lo)
+    }
+}
+
+// Sum of two f64s along with the rounding error
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let bb = s - a;
+    (s, (a - (s - bb)) + (b - bb))
+}
+
+// The same, but only valid when |a| >= |b|
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    (s, b - (s - a))
+}
+
+// Split an f64 into two halves of 26 bits each, whose products are exact
+fn split(a: f64) -> (f64, f64) {
+    let t = 134_217_729.0 * a;
+    let hi = t - (t - a);
+    (hi, a - hi)
+}
+
+// Product of two f64s along with the rounding error (Dekker's algorithm)
+fn two_prod(a: f64, b: f64) -> (f64, f64) {
+    let p = a * b;
+    let ((ah, al), (bh, bl)) = (split(a), split(b));
+    (p, ((ah * bh - p) + ah * bl + al * bh) + al * bl)
+}
+
+impl Float for DoubleDouble {
+    fn from_f64(value: f64) -> DoubleDouble {
+        DoubleDouble { hi: value, lo: 0.0 }
+    }
+
+    fn from_real(value: &Real) -> DoubleDouble {
+        let hi = value.to_f64();
+        let lo = (value.clone() - Real::from(hi)).to_f64();
+        DoubleDouble { hi, lo }
+    }
+
+    fn to_f64(&self) -> f64 {
+        self.hi + self.lo
+    }
+}
+
+//////////////////////////////////////////////////
+////// Operations for DoubleDouble Numbers ///////
+//////////////////////////////////////////////////
+
+impl Add for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn add(self, other: DoubleDouble) -> DoubleDouble {
+        let (s, e) = two_sum(self.hi, other.hi);
+        let (t, f) = two_sum(self.lo, other.lo);
+        let (s, e) = quick_two_sum(s, e + t);
+        let (hi, lo) = quick_two_sum(s, e + f);
+        DoubleDouble { hi, lo }
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn sub(self, other: DoubleDouble) -> DoubleDouble {
+        self + -other
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn mul(self, other: DoubleDouble) -> DoubleDouble {
+        let (p, e) = two_prod(self.hi, other.hi);
+        let (hi, lo) = quick_two_sum(p, e + (self.hi * other.lo + self.lo * other.hi));
+        DoubleDouble { hi, lo }
+    }
+}
+
+impl Div for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn div(self, other: DoubleDouble) -> DoubleDouble {
+        // Long division, one f64's worth of quotient at a time
+        let q1 = self.hi / other.hi;
+        let r = self - other * DoubleDouble::from_f64(q1);
+        let q2 = r.hi / other.hi;
+        let r = r - other * DoubleDouble::from_f64(q2);
+        let q3 = r.hi / other.hi;
+
+        let (hi, lo) = quick_two_sum(q1, q2);
+        DoubleDouble { hi, lo } + DoubleDouble::from_f64(q3)
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn neg(self) -> DoubleDouble {
+        DoubleDouble {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+}
+
+impl PartialEq for DoubleDouble {
+    fn eq(&self, other: &DoubleDouble) -> bool {
+        self.hi == other.hi && self.lo == other.lo
+    }
+}
+
+impl PartialOrd for DoubleDouble {
+    fn partial_cmp(&self, other: &DoubleDouble) -> Option<Ordering> {
+        match self.hi.partial_cmp(&other.hi) {
+            Some(Ordering::Equal) => self.lo.partial_cmp(&other.lo),
+            ordering => ordering,
+        }
+    }
+}
+
+// Allow for parsing from user input, keeping all of the precision a DoubleDouble has
+impl FromStr for DoubleDouble {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Every digit is worth less than 4 bits
+        let precision = cmp::max(s.len() as u32 * 4, PARSE_PRECISION);
+        Ok(DoubleDouble::from_real(&Real::parse(s, precision)?))
+    }
+}
+
+// Print the shortest decimal that parses back to the same number
+impl fmt::Display for DoubleDouble {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let real = self.to_real();
+
+        // 34 digits are always enough, unless lo is far below hi
+        for digits in 1..=MAX_DIGITS {
+            let formatted = real.to_string_with_digits(digits);
+            if formatted.parse::<DoubleDouble>() == Ok(*self) {
+                return write!(f, "{}", formatted);
+            }
+        }
+
+        write!(f, "{}", real)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Enough bits to hold any sum or product below exactly
+    const EXACT: u32 = 512;
+
+    fn exact(hi: f64, lo: f64) -> Real {
+        Real::from(hi).with_precision(EXACT) + Real::from(lo)
+    }
+
+    fn exact_product(a: f64, b: f64) -> Real {
+        Real::from(a).with_precision(EXACT) * Real::from(b)
+    }
+
+    // Relative error of a DoubleDouble against the exact value
+    fn error(value: DoubleDouble, expected: &Real) -> f64 {
+        ((exact(value.hi, value.lo) - expected.clone()) / expected.clone())
+            .to_f64()
+            .abs()
+    }
+
+    const PAIRS: [(f64, f64); 7] = [
+        (1.0, 1e-20),
+        (0.1, 0.2),
+        (1e300, -3e290),
+        (-3.5, 3.5),
+        (1.0 + f64::EPSILON, 1.0 - f64::EPSILON),
+        (123456789.123, -0.000987654321),
+        (5e-324, 1e-310),
+    ];
+
+    #[test]
+    fn two_sum_is_exact() {
+        for &(a, b) in &PAIRS {
+            for &(a, b) in &[(a, b), (b, a)] {
+                let (s, e) = two_sum(a, b);
+                assert_eq!(s, a + b);
+                assert_eq!(exact(s, e), exact(a, b), "{} + {}", a, b);
+
+                if a.abs() >= b.abs() {
+                    assert_eq!(quick_two_sum(a, b), (s, e));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn two_prod_is_exact() {
+        // Dekker's algorithm needs products that neither overflow nor underflow
+        for &(a, b) in PAIRS
+            .iter()
+            .filter(|(a, b)| (a * b).abs() > 1e-290 && (a * b).abs() < 1e290)
+        {
+            let (p, e) = two_prod(a, b);
+            assert_eq!(p, a * b);
+            assert_eq!(exact(p, e), exact_product(a, b), "{} * {}", a, b);
+        }
+
+        // The product's error is below the bottom of an f64
+        let (p, e) = two_prod(1.0 + f64::EPSILON, 1.0 - f64::EPSILON);
+        assert_eq!((p, e), (1.0, -f64::EPSILON * f64::EPSILON));
+    }
+
+    #[test]
+    fn split_halves_are_exact() {
+        for &a in &[0.1, -1.0 / 3.0, 1e150, 7e-150] {
+            let (hi, lo) = split(a);
+            assert_eq!(hi + lo, a);
+            assert_eq!(two_prod(hi, hi).1, 0.0);
+            assert_eq!(two_prod(lo, lo).1, 0.0);
+            assert_eq!(two_prod(hi, lo).1, 0.0);
+        }
+    }
+
+    #[test]
+    fn conversions() {
+        let tenth = Real::parse("0.1", 256).unwrap();
+        let value = DoubleDouble::from_real(&tenth);
+        assert_eq!(value.hi, 0.1);
+        assert!(value.lo != 0.0);
+        assert!(error(value, &tenth) < 2f64.powi(-105));
+        assert_eq!(value.to_f64(), 0.1);
+
+        assert_eq!(
+            DoubleDouble::from_f64(-2.5),
+            DoubleDouble { hi: -2.5, lo: 0.0 }
+        );
+        assert_eq!(DoubleDouble::from_real(&Real::zero(256)).to_f64(), 0.0);
+        assert_eq!(value.to_real(), exact(value.hi, value.lo));
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        let third = DoubleDouble::from_f64(1.0) / DoubleDouble::from_f64(3.0);
+        let sum = DoubleDouble::from_f64(1.0) + DoubleDouble::from_f64(2f64.powi(-80));
+        for &value in &[
+            DoubleDouble::from_f64(0.0),
+            DoubleDouble::from_f64(-2.5),
+            "0.1".parse().unwrap(),
+            third,
+            -third,
+            sum,
+            "1.234567890123456789012345678901e-200".parse().unwrap(),
+        ] {
+            let printed = value.to_string();
+            assert_eq!(printed.parse::<DoubleDouble>(), Ok(value), "{}", printed);
+        }
+
+        // The shortest digits are printed, even when lo holds more
+        assert_eq!("0.1".parse::<DoubleDouble>().unwrap().to_string(), "0.1");
+        assert_eq!(DoubleDouble::from_f64(-2.5).to_string(), "-2.5");
+        assert!("1.2.3".parse::<DoubleDouble>().is_err());
+    }
+
+    #[test]
+    fn arithmetic() {
+        let real = |s: &str| Real::parse(s, 256).unwrap();
+        let (a, b) = (
+            real("1.234567890123456789012345678901"),
+            real("-9.87654321098765432109876e-10"),
+        );
+        let (x, y) = (DoubleDouble::from_real(&a), DoubleDouble::from_real(&b));
+
+        assert!(error(x + y, &(a.clone() + b.clone())) < 2f64.powi(-104));
+        assert!(error(x - y, &(a.clone() - b.clone())) < 2f64.powi(-104));
+        assert!(error(x * y, &(a.clone() * b.clone())) < 2f64.powi(-103));
+        assert!(error(x / y, &(a.clone() / b.clone())) < 2f64.powi(-102));
+        assert!(error(y / x, &(b / a)) < 2f64.powi(-102));
+
+        // Bits far below hi are kept in lo, unlike with an f64
+        let small = DoubleDouble::from_f64(2f64.powi(-80));
+        let sum = DoubleDouble::from_f64(1.0) + small;
+        assert_eq!((sum.hi, sum.lo), (1.0, 2f64.powi(-80)));
+        assert_eq!(sum - DoubleDouble::from_f64(1.0), small);
+
+        let third = DoubleDouble::from_f64(1.0) / DoubleDouble::from_f64(3.0);
+        let one = third * DoubleDouble::from_f64(3.0);
+        assert!((one - DoubleDouble::from_f64(1.0)).to_f64().abs() < 2f64.powi(-104));
+        assert_eq!(
+            third,
+            DoubleDouble {
+                hi: 1.0 / 3.0,
+                lo: 1.0 / 3.0 * 2f64.powi(-54)
+            }
+        );
+        assert_eq!(-third + third, DoubleDouble::from_f64(0.0));
+    }
+}
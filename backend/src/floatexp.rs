@@ -0,0 +1,328 @@
+use std::cmp::Ordering;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::math::Float;
+use crate::real::{ldexp, Real};
+
+#[derive(Clone, Copy, Debug)]
+// Floating-point number with an f64 mantissa and a separate 64-bit exponent.  It has the precision
+// of an f64, but (like a Real) can't realistically overflow or underflow, and is much faster than a
+// Real.  This is what perturbation deltas are stored in once the window is too small for f64s.
+pub struct FloatExp {
+    // Mantissa, normalised so that its absolute value lies in [0.5, 1), unless the number is zero
+    mantissa: f64,
+    // The number is mantissa * 2^exponent.  Zero has an exponent of zero.
+    exponent: i64,
+}
+
+impl FloatExp {
+    // Build a number from any mantissa and exponent, normalising the mantissa
+    fn new(mantissa: f64, exponent: i64) -> FloatExp {
+        if mantissa == 0.0 || !mantissa.is_finite() {
+            return FloatExp {
+                mantissa,
+                exponent: 0,
+            };
+        }
+
+        // Scale subnormals up so that the exponent bits can be read straight off
+        let (mantissa, exponent) = if mantissa.abs() < f64::MIN_POSITIVE {
+            (mantissa * 2f64.powi(64), exponent - 64)
+        } else {
+            (mantissa, exponent)
+        };
+
+        // Replace the f64's own exponent with -1, which puts it in [0.5, 1)
+        let bits = mantissa.to_bits();
+        let shift = ((bits >> 52) & 0x7ff) as i64 - 1022;
+        let mantissa = f64::from_bits((bits & !(0x7ff << 52)) | (1022 << 52));
+
+        FloatExp {
+            mantissa,
+            exponent: exponent + shift,
+        }
+    }
+
+    // Binary exponent of the number, such that its absolute value lies in
+    // [2^(exponent-1), 2^exponent).  Zero has an exponent of zero.
+    pub fn exponent(&self) -> i64 {
+        self.exponent
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa == 0.0
+    }
+}
+
+impl Float for FloatExp {
+    fn from_f64(value: f64) -> FloatExp {
+        FloatExp::new(value, 0)
+    }
+
+    fn from_real(value: &Real) -> FloatExp {
+        let (mantissa, exponent) = value.to_f64_exp();
+        FloatExp::new(mantissa, exponent)
+    }
+
+    fn to_f64(&self) -> f64 {
+        ldexp(self.mantissa, self.exponent)
+    }
+}
+
+impl From<f64> for FloatExp {
+    fn from(value: f64) -> FloatExp {
+        FloatExp::from_f64(value)
+    }
+}
+
+impl From<FloatExp> for f64 {
+    fn from(value: FloatExp) -> f64 {
+        value.to_f64()
+    }
+}
+
+//////////////////////////////////////////////////
+//////// Operations for FloatExp Numbers /////////
+//////////////////////////////////////////////////
+
+impl Add for FloatExp {
+    type Output = FloatExp;
+
+    fn add(self, other: FloatExp) -> FloatExp {
+        if other.is_zero() {
+            return self;
+        } else if self.is_zero() {
+            return other;
+        }
+
+        let (big, small) = if self.exponent >= other.exponent {
+            (self, other)
+        } else {
+            (other, self)
+        };
+
+        // The smaller number doesn't affect the result at all once it's this far below
+        let shift = big.exponent - small.exponent;
+        if shift > 64 {
+            return big;
+        }
+
+        FloatExp::new(
+            big.mantissa + small.mantissa * 2f64.powi(-(shift as i32)),
+            big.exponent,
+        )
+    }
+}
+
+impl Sub for FloatExp {
+    type Output = FloatExp;
+
+    fn sub(self, other: FloatExp) -> FloatExp {
+        self + -other
+    }
+}
+
+impl Mul for FloatExp {
+    type Output = FloatExp;
+
+    fn mul(self, other: FloatExp) -> FloatExp {
+        FloatExp::new(
+            self.mantissa * other.mantissa,
+            self.exponent + other.exponent,
+        )
+    }
+}
+
+impl Div for FloatExp {
+    type Output = FloatExp;
+
+    fn div(self, other: FloatExp) -> FloatExp {
+        FloatExp::new(
+            self.mantissa / other.mantissa,
+            self.exponent - other.exponent,
+        )
+    }
+}
+
+impl Neg for FloatExp {
+    type Output = FloatExp;
+
+    fn neg(self) -> FloatExp {
+        FloatExp {
+            mantissa: -self.mantissa,
+            ..self
+        }
+    }
+}
+
+impl PartialEq for FloatExp {
+    fn eq(&self, other: &FloatExp) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for FloatExp {
+    fn partial_cmp(&self, other: &FloatExp) -> Option<Ordering> {
+        // Infinities and NaNs keep an exponent of zero, so just compare their mantissas
+        if !self.mantissa.is_finite() || !other.mantissa.is_finite() {
+            return self.mantissa.partial_cmp(&other.mantissa);
+        }
+
+        let sign = |value: &FloatExp| match value.mantissa {
+            m if m > 0.0 => 1,
+            m if m < 0.0 => -1,
+            _ => 0,
+        };
+
+        let (a, b) = (sign(self), sign(other));
+        if a != b || a == 0 {
+            return Some(a.cmp(&b));
+        }
+
+        // With the same sign, the larger exponent has the larger magnitude
+        let ordering = self.exponent.cmp(&other.exponent).then(
+            self.mantissa
+                .abs()
+                .partial_cmp(&other.mantissa.abs())
+                .unwrap(),
+        );
+
+        if a > 0 {
+            Some(ordering)
+        } else {
+            Some(ordering.reverse())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(value: FloatExp) -> (f64, i64) {
+        (value.mantissa, value.exponent)
+    }
+
+    // 2^exponent, as a FloatExp
+    fn pow2(exponent: i64) -> FloatExp {
+        FloatExp::new(0.5, exponent + 1)
+    }
+
+    #[test]
+    fn normalisation() {
+        assert_eq!(parts(FloatExp::new(3.0, 5)), (0.75, 7));
+        assert_eq!(parts(FloatExp::new(-1.0, -10)), (-0.5, -9));
+        assert_eq!(parts(FloatExp::new(0.5, 0)), (0.5, 0));
+        assert_eq!(parts(FloatExp::new(0.1, 0)), (0.8, -3));
+        assert_eq!(
+            parts(FloatExp::new(1024.0, i64::MIN / 2)),
+            (0.5, i64::MIN / 2 + 11)
+        );
+
+        // Subnormals are normalised like everything else
+        assert_eq!(parts(FloatExp::from(5e-324)), (0.5, -1073));
+        assert_eq!(parts(FloatExp::from(-3.0 * 5e-324)), (-0.75, -1072));
+
+        // Results of arithmetic are normalised too
+        assert_eq!(parts(FloatExp::from(0.5) * FloatExp::from(0.5)), (0.5, -1));
+        assert_eq!(
+            parts(FloatExp::from(0.75) + FloatExp::from(0.75)),
+            (0.75, 1)
+        );
+        assert_eq!(
+            parts(FloatExp::from(1.0) / FloatExp::from(3.0)),
+            (2.0 / 3.0, -1)
+        );
+    }
+
+    #[test]
+    fn zero() {
+        for &value in &[0.0, -0.0] {
+            let zero = FloatExp::from(value);
+            assert!(zero.is_zero());
+            assert_eq!(zero.exponent(), 0);
+            assert_eq!(zero.to_f64(), 0.0);
+        }
+
+        let zero = FloatExp::from_real(&Real::zero(128));
+        assert!(zero.is_zero());
+        assert_eq!(zero.exponent(), 0);
+
+        let x = pow2(-5000);
+        assert!((x - x).is_zero());
+        assert_eq!((x - x).exponent(), 0);
+        assert_eq!(x + zero, x);
+        assert_eq!(zero + x, x);
+        assert!((zero * x).is_zero());
+        assert!(zero < x && -x < zero);
+    }
+
+    #[test]
+    fn different_exponents() {
+        let one = FloatExp::from(1.0);
+
+        // As far apart as an f64's mantissa holds, and then too far to make any difference
+        assert!(one + pow2(-52) > one);
+        assert_eq!((one + pow2(-52)).to_f64(), 1.0 + f64::EPSILON);
+        assert_eq!(one + pow2(-54), one);
+        assert_eq!(one + pow2(-64), one);
+        assert_eq!(one + pow2(-65), one);
+        assert_eq!(one - pow2(-1000), one);
+        assert_eq!(pow2(-1000) + one, one);
+        assert_eq!(pow2(-1000) - one, -one);
+
+        // Exponents far outside the range of an f64 work like any other
+        let (a, b) = (pow2(-5000), pow2(-5001));
+        assert_eq!(parts(a + b), (0.75, -4999));
+        assert_eq!(parts(a - b), (0.5, -5000));
+        assert_eq!(parts(b - a), (-0.5, -5000));
+        assert_eq!(parts(a * pow2(5000)), (0.5, 1));
+        assert_eq!(parts(a / pow2(5000)), (0.5, -9999));
+        assert!(b < a && -a < -b);
+    }
+
+    #[test]
+    fn f64_boundary() {
+        // Normal numbers, near the bottom of the range and around the top
+        for &value in &[
+            1.0,
+            -0.1,
+            2f64.powi(-960),
+            -1.5 * 2f64.powi(-1022),
+            f64::MAX,
+        ] {
+            assert_eq!(FloatExp::from(value).to_f64(), value);
+        }
+
+        // Subnormals lose precision as they go, down to 2^-1074, below which they're zero
+        assert_eq!(pow2(-1074).to_f64(), 5e-324);
+        assert_eq!(pow2(-1070).to_f64(), 16.0 * 5e-324);
+        assert_eq!(FloatExp::new(0.625, -1073).to_f64(), 5e-324);
+        assert_eq!(FloatExp::new(0.75, -1073).to_f64(), 1e-323);
+        assert_eq!(pow2(-1076).to_f64(), 0.0);
+        assert_eq!(pow2(-5000).to_f64(), 0.0);
+        assert_eq!((-pow2(-5000)).to_f64(), 0.0);
+
+        // And past the top they're infinite
+        assert_eq!(pow2(1023).to_f64(), 2f64.powi(1023));
+        assert_eq!(pow2(1024).to_f64(), f64::INFINITY);
+        assert_eq!((-pow2(5000)).to_f64(), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn from_real() {
+        let real = |s: &str| Real::parse(s, 128).unwrap();
+
+        assert_eq!(FloatExp::from_real(&real("0.1")).to_f64(), 0.1);
+        assert_eq!(FloatExp::from_real(&real("-1e-300")).to_f64(), -1e-300);
+        assert_eq!(FloatExp::from_real(&real("1e-310")).to_f64(), 1e-310);
+        assert_eq!(FloatExp::from_real(&real("1e308")).to_f64(), 1e308);
+
+        // Far outside the range of an f64, the exponent is kept exactly
+        let tiny = real("1e-1000");
+        assert_eq!(FloatExp::from_real(&tiny).exponent(), tiny.exponent());
+        assert_eq!(FloatExp::from_real(&tiny).to_f64(), 0.0);
+        let ratio = FloatExp::from_real(&tiny) / FloatExp::from_real(&real("1e-1001"));
+        assert!((ratio.to_f64() - 10.0).abs() < 1e-14);
+    }
+}
@@ -0,0 +1,484 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::math::Complex;
+
+#[derive(Clone, PartialEq, Debug)]
+// A formula that a user gave for iterating z, in terms of z and c, such as `z^3-0.5*z+c`
+pub struct Formula {
+    expr: Expr,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+// Parsed form of a formula
+enum Expr {
+    Z,
+    C,
+    // Square root of -1
+    I,
+    Number(f64),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, Box<Expr>),
+    Call(Function, Box<Expr>),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+// Functions that can be called from a formula
+enum Function {
+    Exp,
+    Log,
+    Sin,
+    Cos,
+}
+
+const FUNCTIONS: [(&str, Function); 4] = [
+    ("exp", Function::Exp),
+    ("log", Function::Log),
+    ("sin", Function::Sin),
+    ("cos", Function::Cos),
+];
+
+impl Formula {
+    // Parse a formula given by the user, where "none" means to use the fractal's own formula
+    pub fn parse_option(s: &str) -> Result<Option<Formula>, String> {
+        match s.trim() {
+            "none" => Ok(None),
+            s => s.parse().map(Some),
+        }
+    }
+
+    // Show a formula that may not be set, the same way parse_option takes it
+    pub fn describe(formula: Option<&Formula>) -> String {
+        match formula {
+            Some(formula) => formula.to_string(),
+            None => "none".into(),
+        }
+    }
+
+    // Work out the next value of z
+    pub fn evaluate(&self, z: Complex, c: Complex) -> Complex {
+        self.expr.evaluate(z, c)
+    }
+}
+
+impl Expr {
+    fn evaluate(&self, z: Complex, c: Complex) -> Complex {
+        match self {
+            Expr::Z => z,
+            Expr::C => c,
+            Expr::I => Complex(0.0, 1.0),
+            Expr::Number(value) => Complex(*value, 0.0),
+            Expr::Neg(a) => {
+                let a = a.evaluate(z, c);
+                Complex(-a.0, -a.1)
+            }
+            Expr::Add(a, b) => a.evaluate(z, c) + b.evaluate(z, c),
+            Expr::Sub(a, b) => a.evaluate(z, c) - b.evaluate(z, c),
+            Expr::Mul(a, b) => a.evaluate(z, c) * b.evaluate(z, c),
+            Expr::Div(a, b) => a.evaluate(z, c) / b.evaluate(z, c),
+            Expr::Pow(a, b) => a.evaluate(z, c).powc(b.evaluate(z, c)),
+            Expr::Call(function, a) => {
+                let a = a.evaluate(z, c);
+                match function {
+                    Function::Exp => a.exp(),
+                    Function::Log => a.log(),
+                    Function::Sin => a.sin(),
+                    Function::Cos => a.cos(),
+                }
+            }
+        }
+    }
+
+    // How tightly the expression binds, for deciding where parentheses are needed when printing
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Add(..) | Expr::Sub(..) => 1,
+            Expr::Mul(..) | Expr::Div(..) => 2,
+            Expr::Neg(..) => 3,
+            Expr::Pow(..) => 4,
+            _ => 5,
+        }
+    }
+}
+
+//////////////////////////////////////////////////
+//////////////////// Parsing /////////////////////
+//////////////////////////////////////////////////
+
+// Recursive descent parser over the formula's characters, with the grammar
+//   sum     = product (("+" | "-") product)*
+//   product = unary (("*" | "/") unary)*
+//   unary   = "-" unary | power
+//   power   = atom ("^" unary)?
+//   atom    = number | "z" | "c" | "i" | function "(" sum ")" | "(" sum ")"
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    // Skip any whitespace, then look at the next character
+    fn peek(&mut self) -> Option<char> {
+        while self.position < self.chars.len() && self.chars[self.position].is_whitespace() {
+            self.position += 1;
+        }
+
+        self.chars.get(self.position).cloned()
+    }
+
+    // Take the next character if it's the given one
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error<T>(&mut self) -> Result<T, String> {
+        match self.peek() {
+            Some(found) => Err(format!(
+                "Unexpected {} at position {} of formula.",
+                found,
+                self.position + 1
+            )),
+            None => Err("Unexpected end of formula.".into()),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+
+        loop {
+            if self.eat('+') {
+                expr = Expr::Add(Box::new(expr), Box::new(self.product()?));
+            } else if self.eat('-') {
+                expr = Expr::Sub(Box::new(expr), Box::new(self.product()?));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+
+        loop {
+            if self.eat('*') {
+                expr = Expr::Mul(Box::new(expr), Box::new(self.unary()?));
+            } else if self.eat('/') {
+                expr = Expr::Div(Box::new(expr), Box::new(self.unary()?));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.atom()?;
+
+        // Powers are right associative, so z^2^3 is z^(2^3)
+        if self.eat('^') {
+            Ok(Expr::Pow(Box::new(base), Box::new(self.unary()?)))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some('(') => {
+                self.position += 1;
+                let expr = self.sum()?;
+                if !self.eat(')') {
+                    return self.error();
+                }
+                Ok(expr)
+            }
+            Some(ch) if ch.is_ascii_digit() || ch == '.' => self.number(),
+            Some(ch) if ch.is_ascii_alphabetic() => self.name(),
+            _ => self.error(),
+        }
+    }
+
+    fn number(&mut self) -> Result<Expr, String> {
+        let start = self.position;
+        let digits = |chars: &[char], mut position: usize| {
+            while position < chars.len()
+                && (chars[position].is_ascii_digit() || chars[position] == '.')
+            {
+                position += 1;
+            }
+            position
+        };
+        self.position = digits(&self.chars, self.position);
+
+        // Allow an exponent, as long as there are digits after it
+        if let Some('e') | Some('E') = self.chars.get(self.position) {
+            let mut end = self.position + 1;
+            if let Some('+') | Some('-') = self.chars.get(end) {
+                end += 1;
+            }
+            if self.chars.get(end).is_some_and(|ch| ch.is_ascii_digit()) {
+                self.position = digits(&self.chars, end);
+            }
+        }
+
+        let number: String = self.chars[start..self.position].iter().collect();
+        match number.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(Expr::Number(value)),
+            _ => Err(format!("Invalid number in formula: {}.", number)),
+        }
+    }
+
+    fn name(&mut self) -> Result<Expr, String> {
+        let start = self.position;
+        while self.position < self.chars.len() && self.chars[self.position].is_ascii_alphanumeric()
+        {
+            self.position += 1;
+        }
+        let name: String = self.chars[start..self.position].iter().collect();
+
+        match name.as_str() {
+            "z" => Ok(Expr::Z),
+            "c" => Ok(Expr::C),
+            "i" => Ok(Expr::I),
+            _ => match FUNCTIONS.iter().find(|(string, _)| *string == name) {
+                Some((_, function)) => {
+                    if !self.eat('(') {
+                        return Err(format!("Expected ( after {} in formula.", name));
+                    }
+                    let argument = self.sum()?;
+                    if !self.eat(')') {
+                        return self.error();
+                    }
+                    Ok(Expr::Call(*function, Box::new(argument)))
+                }
+                None => Err(format!(
+                    "Unknown variable or function in formula: {}.",
+                    name
+                )),
+            },
+        }
+    }
+}
+
+// Allow for parsing formulas from user input
+impl FromStr for Formula {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            chars: s.chars().collect(),
+            position: 0,
+        };
+
+        let expr = parser.sum()?;
+
+        // Anything left over means the formula didn't parse all the way through
+        if parser.peek().is_some() {
+            return parser.error();
+        }
+
+        Ok(Formula { expr })
+    }
+}
+
+//////////////////////////////////////////////////
+/////////////////// Printing /////////////////////
+//////////////////////////////////////////////////
+
+// Formulas are printed without any whitespace, and with only the parentheses that are needed, so
+// that they parse back to the same formula and fit in a single part of a GUI command
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.expr)
+    }
+}
+
+// Print an operand, in parentheses if it doesn't bind at least as tightly as the given precedence
+fn operand(f: &mut fmt::Formatter, expr: &Expr, precedence: u8) -> fmt::Result {
+    if expr.precedence() < precedence {
+        write!(f, "({})", expr)
+    } else {
+        write!(f, "{}", expr)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Operators are left associative (apart from ^), so the right-hand side of - and / needs
+        // parentheses around operators of the same precedence
+        let (symbol, a, b, left, right) = match self {
+            Expr::Z => return write!(f, "z"),
+            Expr::C => return write!(f, "c"),
+            Expr::I => return write!(f, "i"),
+            Expr::Number(value) => return write!(f, "{}", value),
+            Expr::Neg(a) => {
+                write!(f, "-")?;
+                return operand(f, a, 3);
+            }
+            Expr::Call(function, a) => {
+                let name = FUNCTIONS
+                    .iter()
+                    .find(|(_, other)| other == function)
+                    .unwrap()
+                    .0;
+                return write!(f, "{}({})", name, a);
+            }
+            Expr::Add(a, b) => ('+', a, b, 1, 2),
+            Expr::Sub(a, b) => ('-', a, b, 1, 2),
+            Expr::Mul(a, b) => ('*', a, b, 2, 3),
+            Expr::Div(a, b) => ('/', a, b, 2, 3),
+            Expr::Pow(a, b) => ('^', a, b, 5, 3),
+        };
+
+        operand(f, a, left)?;
+        write!(f, "{}", symbol)?;
+        operand(f, b, right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Formula {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn round_trips() {
+        let formulas = [
+            "z^2+c",
+            "-z^2",
+            "z^-2",
+            "(-z)^2",
+            "z^2^3",
+            "(z^2)^3",
+            "z-(c-1)",
+            "(z-c)-1",
+            "z/(c/2)",
+            "z--c",
+            "--z",
+            "z*-c",
+            "2^-z",
+            "-exp(z)^2",
+            "sin(z*i)/cos(z-c)+log(c)",
+            "0.5*z^3+1e-300*c-1000000000000000000000",
+        ];
+
+        for formula in &formulas {
+            let parsed = parse(formula);
+            let printed = parsed.to_string();
+            assert_eq!(
+                parse(&printed),
+                parsed,
+                "{} printed as {}",
+                formula,
+                printed
+            );
+        }
+    }
+
+    #[test]
+    fn prints_only_the_parentheses_needed() {
+        let printed = |s: &str| parse(s).to_string();
+
+        assert_eq!(printed("z ^ 2 + c"), "z^2+c");
+        assert_eq!(printed("-z^2"), "-z^2");
+        assert_eq!(printed("-(z^2)"), "-z^2");
+        assert_eq!(printed("(-z)^2"), "(-z)^2");
+        assert_eq!(printed("z^(2^3)"), "z^2^3");
+        assert_eq!(printed("(z^2)^3"), "(z^2)^3");
+        assert_eq!(printed("z^(-2)"), "z^-2");
+        assert_eq!(printed("(z-c)-1"), "z-c-1");
+        assert_eq!(printed("z-(c-1)"), "z-(c-1)");
+        assert_eq!(printed("z-(c+1)"), "z-(c+1)");
+        assert_eq!(printed("z+(c-1)"), "z+(c-1)");
+        assert_eq!(printed("(z*c)/2"), "z*c/2");
+        assert_eq!(printed("z/(c*2)"), "z/(c*2)");
+        assert_eq!(printed("(z+c)*(z-c)"), "(z+c)*(z-c)");
+        assert_eq!(printed("2e3*z"), "2000*z");
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(parse("-z^2"), parse("-(z^2)"));
+        assert_eq!(parse("z^2^3"), parse("z^(2^3)"));
+        assert_eq!(parse("z-c-1"), parse("(z-c)-1"));
+        assert_eq!(parse("z/c/2"), parse("(z/c)/2"));
+        assert_eq!(parse("z+c*2"), parse("z+(c*2)"));
+        assert!(parse("z-c-1") != parse("z-(c-1)"));
+        assert!(parse("-z^2") != parse("(-z)^2"));
+    }
+
+    #[test]
+    fn evaluates() {
+        let (z, c) = (Complex(1.0, 2.0), Complex(-0.5, 0.25));
+        let evaluate = |s: &str| parse(s).evaluate(z, c);
+
+        assert_eq!(evaluate("z^2+c"), Complex(-3.5, 4.25));
+        assert_eq!(evaluate("z*z+c"), Complex(-3.5, 4.25));
+        assert_eq!(evaluate("-z^2"), Complex(3.0, -4.0));
+        assert_eq!(evaluate("(-z)^2"), Complex(-3.0, 4.0));
+        assert_eq!(evaluate("z-(c-1)"), Complex(2.5, 1.75));
+        assert_eq!(evaluate("z-c-1"), Complex(0.5, 1.75));
+        assert_eq!(evaluate("i*i"), Complex(-1.0, 0.0));
+        assert_eq!(evaluate("2^3^2"), Complex(512.0, 0.0));
+
+        let inverse = evaluate("z^-2") * evaluate("z^2");
+        assert!((inverse - Complex(1.0, 0.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |s: &str| s.parse::<Formula>().unwrap_err();
+
+        assert_eq!(error("x+c"), "Unknown variable or function in formula: x.");
+        assert_eq!(
+            error("sqrt(z)"),
+            "Unknown variable or function in formula: sqrt."
+        );
+        assert_eq!(error("zc"), "Unknown variable or function in formula: zc.");
+        assert_eq!(error("exp z"), "Expected ( after exp in formula.");
+        assert_eq!(error("1.2.3*z"), "Invalid number in formula: 1.2.3.");
+        assert_eq!(error("z+."), "Invalid number in formula: ..");
+        assert_eq!(error("1e999*z"), "Invalid number in formula: 1e999.");
+        assert_eq!(error("(z+1"), "Unexpected end of formula.");
+        assert_eq!(error("exp(z"), "Unexpected end of formula.");
+        assert_eq!(error("z+1)"), "Unexpected ) at position 4 of formula.");
+        assert_eq!(error("((z)"), "Unexpected end of formula.");
+        assert_eq!(error("()"), "Unexpected ) at position 2 of formula.");
+        assert_eq!(error(""), "Unexpected end of formula.");
+        assert_eq!(error("z+"), "Unexpected end of formula.");
+        assert_eq!(error("z**2"), "Unexpected * at position 3 of formula.");
+        assert_eq!(error("2z"), "Unexpected z at position 2 of formula.");
+        assert_eq!(error("1e"), "Unexpected e at position 2 of formula.");
+        assert_eq!(
+            Formula::parse_option("z^"),
+            Err("Unexpected end of formula.".into())
+        );
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(Formula::parse_option(" none "), Ok(None));
+        assert_eq!(Formula::parse_option("z^3+c"), Ok(Some(parse("z^3+c"))));
+        assert_eq!(Formula::describe(None), "none");
+        assert_eq!(Formula::describe(Some(&parse("z ^ 3 + c"))), "z^3+c");
+    }
+}
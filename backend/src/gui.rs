@@ -1,5 +1,8 @@
 use crate::util::Config;
 
+use crate::compute::Backend;
+use crate::formula::Formula;
+use crate::trap::Trap;
 use crate::render::*;
 
 use crate::math::*;
@@ -26,26 +29,26 @@ pub fn begin(config: Config) {
     // Begin comm loop
     for stream in listener.incoming().take(1) {
         match stream {
-            Ok(stream) => handle_connection(stream).unwrap(),
+            Ok(stream) => handle_connection(stream, config.backend).unwrap(),
             Err(e) => println!("{:?}", e)
         }
     }
 }
 
-fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
+fn handle_connection(mut stream: TcpStream, backend: Backend) -> std::io::Result<()> {
     // Create the Render object
     let mut render = Render::default();
 
     let mut current_operation: Option<RenderJob> = None;
 
     // Send a message on the stream to indicate that the backend is ready
-    stream.write("ready\n".as_bytes())?;
+    stream.write_all("ready\n".as_bytes())?;
 
     // Begin loop
     loop {
         // Read a line
         let mut buf = vec![0u8; 1024];
-        stream.read(&mut buf)?;
+        let _ = stream.read(&mut buf)?;
         let line = String::from_utf8_lossy(&buf[..]);
 
         // Remove null bytes from end of string
@@ -55,22 +58,22 @@ fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
             // Make sure there isn't already an operatin in progress
             if let Some(ref mut operation) = current_operation {
                 // See if the operation is done
-                if let None = operation.progress() {
+                if operation.progress().is_none() {
                     // It's done
                     current_operation = None;
                 } else {
-                    stream.write("error(5)\n".as_bytes())?;
+                    stream.write_all("error(5)\n".as_bytes())?;
                     continue
                 }
             }
 
             // render [iterations] [width] [height] [supersampling] [centerx] [centery] [radius] \
-            //   [colorfunc]
+            //   [colorfunc] [option=value]...
             let parts: Vec<_> = line.split(" ").collect();
 
-            // Ensure there are 9 parts
-            if parts.len() != 9 {
-                stream.write("error(1)\n".as_bytes())?;
+            // Ensure there are at least 9 parts
+            if parts.len() < 9 {
+                stream.write_all("error(1)\n".as_bytes())?;
                 continue
             }
 
@@ -78,7 +81,7 @@ fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
             let iterations = match parts[1].parse::<u32>() {
                 Ok(value) => value,
                 Err(_) => {
-                    stream.write("error(2.1)\n".as_bytes())?;
+                    stream.write_all("error(2.1)\n".as_bytes())?;
                     continue
                 }
             };
@@ -87,7 +90,7 @@ fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
             let width = match parts[2].parse::<u32>() {
                 Ok(value) => value,
                 Err(_) => {
-                    stream.write("error(2.2)\n".as_bytes())?;
+                    stream.write_all("error(2.2)\n".as_bytes())?;
                     continue
                 }
             };
@@ -96,7 +99,7 @@ fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
             let height = match parts[3].parse::<u32>() {
                 Ok(value) => value,
                 Err(_) => {
-                    stream.write("error(2.3)\n".as_bytes())?;
+                    stream.write_all("error(2.3)\n".as_bytes())?;
                     continue
                 }
             };
@@ -105,7 +108,7 @@ fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
             let supersampling = match parts[4].parse::<u32>() {
                 Ok(value) => value,
                 Err(_) => {
-                    stream.write("error(2.4)\n".as_bytes())?;
+                    stream.write_all("error(2.4)\n".as_bytes())?;
                     continue
                 }
             };
@@ -114,7 +117,7 @@ fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
             let centerx = match parts[5].parse::<Real>() {
                 Ok(value) => value,
                 Err(_) => {
-                    stream.write("error(2.5)\n".as_bytes())?;
+                    stream.write_all("error(2.5)\n".as_bytes())?;
                     continue
                 }
             };
@@ -123,7 +126,7 @@ fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
             let centery = match parts[6].parse::<Real>() {
                 Ok(value) => value,
                 Err(_) => {
-                    stream.write("error(2.6)\n".as_bytes())?;
+                    stream.write_all("error(2.6)\n".as_bytes())?;
                     continue
                 }
             };
@@ -132,7 +135,7 @@ fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
             let radius = match parts[7].parse::<Real>() {
                 Ok(value) => value,
                 Err(_) => {
-                    stream.write("error(2.7)\n".as_bytes())?;
+                    stream.write_all("error(2.7)\n".as_bytes())?;
                     continue
                 }
             };
@@ -142,19 +145,19 @@ fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
                 Ok(value) => value,
                 Err(e) => {
                     println!("{}", e);
-                    stream.write("error(2.8)\n".as_bytes())?;
+                    stream.write_all("error(2.8)\n".as_bytes())?;
                     continue
                 }
             };
 
             // Validate range of supersampling
             if supersampling == 0 {
-                stream.write("error(3.1)\n".as_bytes())?;
+                stream.write_all("error(3.1)\n".as_bytes())?;
             }
 
             // Validate range of radius
-            if radius < 0.0 {
-                stream.write("error(3.2)\n".as_bytes())?;
+            if radius.is_sign_negative() {
+                stream.write_all("error(3.2)\n".as_bytes())?;
             }
 
             // DEBUG: Log values for debugging
@@ -168,68 +171,192 @@ fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
             println!("Color function: {}", colorfunc.info());
 
             // Apply the given values to the render params
-            let new_params = Parameters {
+            let mut new_params = Parameters {
                 image_size: (width, height),
                 supersampling,
                 center: Complex(centerx, centery),
                 radius,
                 max_iter: iterations,
+                ..Parameters::default()
             };
 
+            // Apply any options given after the color function
+            if let Some(e) = parts[9..]
+                .iter()
+                .find_map(|option| set_option(&mut new_params, option).err())
+            {
+                println!("{}", e);
+                stream.write_all("error(2.9)\n".as_bytes())?;
+                continue
+            }
+
+            // Validate that the window can be rendered precisely enough
+            if let Err(e) = new_params.check_precision() {
+                println!("{}", e);
+                stream.write_all("error(3.3)\n".as_bytes())?;
+                continue
+            }
+            println!("Fractal:        {}", new_params.fractal);
+            println!("Formula:        {}", Formula::describe(new_params.formula.as_ref()));
+
             // Update the render data
             render.recalc(&new_params);
 
             // Tell the front end that the request was valid, and we'll begin rendering
-            stream.write("ok\n".as_bytes())?;
+            stream.write_all("ok\n".as_bytes())?;
 
             // Begin rendering, and set the current operation
-            current_operation = Some(render.clone().run_and_export(colorfunc));
+            current_operation = Some(render.clone().run_and_export(colorfunc, backend));
         } else if line == "progress" {
             if let Some(ref operation) = current_operation {
-                // Get the progress
-                let progress = format!("{}\n", operation.progress().unwrap_or(101.0));
+                // Get the progress, through all of the passes together
+                let passes = operation.passes() as f64;
+                let progress = match operation.progress() {
+                    Some((pass, progress)) => ((pass - 1) as f64 * 100.0 + progress) / passes,
+                    None => 101.0,
+                };
+                let progress = format!("{}\n", progress);
 
                 // Write it back
-                stream.write((&progress[..]).as_bytes())?;
+                stream.write_all(progress.as_bytes())?;
+            } else {
+                // No current operation
+                stream.write_all("error(4)\n".as_bytes())?;
+            }
+        } else if line == "preview" {
+            if let Some(ref operation) = current_operation {
+                // Send the latest pass's preview, if there's a new one
+                match operation.preview() {
+                    Some((pass, _, Some(path))) => {
+                        stream.write_all(format!("{} {}\n", pass, path).as_bytes())?;
+                    }
+                    _ => stream.write_all("error(8.2)\n".as_bytes())?,
+                }
             } else {
                 // No current operation
-                stream.write("error(4)\n".as_bytes())?;
+                stream.write_all("error(8.1)\n".as_bytes())?;
             }
         } else if line == "output" {
             // Make sure an opearation exists
             if let Some(ref mut operation) = current_operation {
                 // Make sure the operation is complete
-                if let Some(_) = operation.progress() {
+                if operation.progress().is_some() {
                     // Operation is still in progress
-                    stream.write("error(6.2)\n".as_bytes())?;
+                    stream.write_all("error(6.2)\n".as_bytes())?;
                 } else {
                     // We good, fetch the result
                     match current_operation.take().unwrap().join() {
                         Ok((new_render, path)) => {
+                            if new_render.guessed() > 0.0 {
+                                println!("Guessed {:.1}% of the pixels.", new_render.guessed() * 100.0);
+                            }
+
                             // Save the render
                             render = new_render;
 
                             // Write the path
                             let response = format!("{}\n", path.unwrap());
-                            stream.write((&response[..]).as_bytes())?;
+                            stream.write_all(response.as_bytes())?;
                         }
                         Err(_) => {
-                            stream.write("error(6.3)\n".as_bytes())?;
+                            stream.write_all("error(6.3)\n".as_bytes())?;
                         }
                     }
                 }
             } else {
                 // No operation
-                stream.write("error(6.1)\n".as_bytes())?;
+                stream.write_all("error(6.1)\n".as_bytes())?;
+            }
+        } else if line == "cancel" {
+            if let Some(ref operation) = current_operation {
+                // Ask the operation to stop, its output will then be an error
+                operation.cancel();
+                stream.write_all("ok\n".as_bytes())?;
+            } else {
+                // No current operation
+                stream.write_all("error(7)\n".as_bytes())?;
             }
         } else if line == "exit" {
-            stream.write("ok\n".as_bytes())?;
+            stream.write_all("ok\n".as_bytes())?;
             break;
         } else {
-            stream.write("error(0)\n".as_bytes())?;
+            stream.write_all("error(0)\n".as_bytes())?;
         }
 
-        // TODO: accept requests for progress, exporting, (maybe even canceling a render?)
+        // TODO: accept requests for exporting
+    }
+
+    Ok(())
+}
+
+// Set one of the optional render parameters, given as option=value
+fn set_option(params: &mut Parameters, option: &str) -> Result<(), String> {
+    let (name, value) = match option.split_once('=') {
+        Some(pair) => pair,
+        None => return Err(format!("Option {} has no value.", option)),
+    };
+
+    match name {
+        "fractal" => params.fractal = value.parse()?,
+        "julia:x" => params.julia.0 = value.parse()?,
+        "julia:y" => params.julia.1 = value.parse()?,
+        "formula" => params.formula = Formula::parse_option(value)?,
+        "roots" => params.polynomial = value.parse()?,
+        "buddhabrot:samples" => {
+            params.buddhabrot.samples = match value.parse() {
+                Ok(samples) => samples,
+                Err(_) => return Err(format!("Invalid number of samples: {}.", value)),
+            }
+        }
+        "buddhabrot:red" => params.buddhabrot.windows[0] = value.parse()?,
+        "buddhabrot:green" => params.buddhabrot.windows[1] = value.parse()?,
+        "buddhabrot:blue" => params.buddhabrot.windows[2] = value.parse()?,
+        "sequence" => params.sequence = value.parse()?,
+        "escape_radius" => {
+            params.escape_radius = match value.parse::<f64>() {
+                Ok(radius) if radius.is_finite() && radius > 0.0 => radius,
+                _ => return Err(format!("Invalid escape radius: {}.", value)),
+            }
+        }
+        "bailout" => params.bailout = value.parse()?,
+        "trap" => params.trap = Trap::parse_option(value)?,
+        "averages" => {
+            params.averages = match value.parse() {
+                Ok(averages) => averages,
+                Err(_) => return Err(format!("Invalid averages: {}.", value)),
+            }
+        }
+        "stripe_density" => {
+            params.stripe_density = match value.parse::<f64>() {
+                Ok(density) if density.is_finite() => density,
+                _ => return Err(format!("Invalid stripe density: {}.", value)),
+            }
+        }
+        "interior_stats" => {
+            params.interior_stats = match value.parse() {
+                Ok(interior_stats) => interior_stats,
+                Err(_) => return Err(format!("Invalid interior stats: {}.", value)),
+            }
+        }
+        "progressive" => {
+            params.progressive = match value.parse() {
+                Ok(progressive) => progressive,
+                Err(_) => return Err(format!("Invalid progressive: {}.", value)),
+            }
+        }
+        "subdivision" => {
+            params.subdivision = match value.parse() {
+                Ok(subdivision) => subdivision,
+                Err(_) => return Err(format!("Invalid subdivision: {}.", value)),
+            }
+        }
+        "power" => {
+            params.power = match value.parse::<f64>() {
+                Ok(power) if power.is_finite() => power,
+                _ => return Err(format!("Invalid power: {}.", value)),
+            }
+        }
+        _ => return Err(format!("No such option: {}.", name)),
     }
 
     Ok(())
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::fs;
 use std::io::prelude::*;
 use std::io::BufReader;
-use std::io::{Error, ErrorKind, Result};
+use std::io::{Error, Result};
 use std::net::TcpStream;
 
 pub struct Target {
@@ -33,7 +33,7 @@ impl Request {
 
         // Ensure all 3 parts are there
         if first_line.len() != 3 {
-            let error = Error::new(ErrorKind::Other, "Invalid First Line");
+            let error = Error::other("Invalid First Line");
             return Err(error);
         }
 
@@ -61,7 +61,7 @@ impl Request {
             reader.read_line(&mut line)?;
 
             // A blank link signals the end of the headers
-            if line.trim().len() == 0 {
+            if line.trim().is_empty() {
                 break;
             }
 
@@ -70,7 +70,7 @@ impl Request {
 
             // Ensure there are 2 parts
             if line.len() != 2 {
-                let error = Error::new(ErrorKind::Other, "Invalid Header");
+                let error = Error::other("Invalid Header");
                 return Err(error);
             }
 
@@ -86,7 +86,7 @@ impl Request {
             let len = match len.parse::<u32>() {
                 Ok(len) => len,
                 Err(_) => {
-                    let error = Error::new(ErrorKind::Other, "Invalid Content-Length");
+                    let error = Error::other("Invalid Content-Length");
                     return Err(error);
                 }
             };
@@ -156,13 +156,13 @@ impl Request {
         content.extend(body);
 
         // Send
-        self.stream.write(&content)?;
+        self.stream.write_all(&content)?;
         self.stream.flush()?;
 
         Ok(content.len())
     }
 
-    pub fn respond_with_file<'a>(
+    pub fn respond_with_file(
         &mut self,
         status: &str,
         mut headers: HashMap<&str, String>,
@@ -1,8 +1,13 @@
 extern crate image;
 
+use crate::buddhabrot::Density;
 use crate::colors::*;
 use crate::render::*;
 
+// Fraction of the pixels hit in each channel of a Buddhabrot that are shown without being clipped
+// to white
+const TONE_PERCENTILE: f64 = 0.999;
+
 // Image structure, created from a render, that stores the pixels as colors
 pub struct Image {
     pub size: (u32, u32),
@@ -12,12 +17,18 @@ pub struct Image {
 
 impl Image {
     pub fn new(render: &Render, color_func: ColorFunction) -> Image {
+        // The Buddhabrot is colored by its hits instead
+        if let Some(density) = &render.density {
+            return Image::density(render, density);
+        }
+
         // Use the provided color function to map each pixel from the render to a color
         let pixels: Vec<_> = render
             .pixels
             .iter()
-            .map(|(i, _, z, _)| {
-                (*color_func.func)(*i, render.iterations, *z)
+            .zip(&render.details)
+            .map(|((i, _, z, _), details)| {
+                (*color_func.func)(*i, render.iterations, *z, details, &render.params)
             })
             .collect();
 
@@ -28,6 +39,94 @@ impl Image {
         }
     }
 
+    // Preview of a render that has only been iterated on every step-th pixel across and down, with
+    // one pixel for each of those
+    pub fn preview(render: &Render, color_func: ColorFunction, step: u32) -> Image {
+        let (width, height) = render.params.image_size;
+        let size = (width.div_ceil(step), height.div_ceil(step));
+
+        let pixels = (0..size.0 * size.1)
+            .map(|idx| {
+                let (x, y) = (idx % size.0 * step, idx / size.0 * step);
+                let idx = (x + y * width) as usize;
+                let (i, _, z, _) = render.pixels[idx];
+                (*color_func.func)(i, render.iterations, z, &render.details[idx], &render.params)
+            })
+            .collect();
+
+        Image {
+            pixels,
+            size,
+            scale: 1,
+        }
+    }
+
+    // Tone-map the Buddhabrot's hits, scaling each channel so that only its brightest pixels are
+    // clipped, and taking the square root so that the fainter orbits still show up
+    fn density(render: &Render, density: &Density) -> Image {
+        let mut white = [1.0; 3];
+        for (channel, white) in white.iter_mut().enumerate() {
+            let mut hits: Vec<f64> = density
+                .hits
+                .iter()
+                .map(|hits| hits[channel])
+                .filter(|hits| *hits > 0.0)
+                .collect();
+
+            if !hits.is_empty() {
+                let idx = ((hits.len() - 1) as f64 * TONE_PERCENTILE) as usize;
+                *white = *hits
+                    .select_nth_unstable_by(idx, |a, b| a.partial_cmp(b).unwrap())
+                    .1;
+            }
+        }
+
+        let pixels = density
+            .hits
+            .iter()
+            .map(|hits| {
+                let tone = |channel: usize| (hits[channel] / white[channel]).min(1.0).sqrt() as f32;
+                Color::RGB(tone(0), tone(1), tone(2))
+            })
+            .collect();
+
+        Image {
+            pixels,
+            size: render.params.image_size,
+            scale: render.params.supersampling,
+        }
+    }
+
+    // Print the image to the terminal, scaled to fit the given number of columns, with 24-bit color
+    // escape codes.  Each character is an upper half block, which shows two pixels one above the
+    // other as its foreground and background colors.
+    pub fn print(&self, columns: u32) {
+        let columns = std::cmp::min(columns, self.size.0);
+        let scale = self.size.0 as f64 / columns as f64;
+        let rows = std::cmp::max((self.size.1 as f64 / scale / 2.0) as u32, 1);
+
+        let color = |x: u32, y: u32| {
+            let x = std::cmp::min((x as f64 * scale) as u32, self.size.0 - 1);
+            let y = std::cmp::min((y as f64 * scale) as u32, self.size.1 - 1);
+            let (r, g, b) = self.pixels[(x + y * self.size.0) as usize].rgb();
+            ((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8)
+        };
+
+        let mut text = String::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                let (top, bottom) = (color(column, row * 2), color(column, row * 2 + 1));
+                text.push_str(&format!(
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                    top.0, top.1, top.2, bottom.0, bottom.1, bottom.2
+                ));
+            }
+            text.push_str("\x1b[0m\n");
+        }
+
+        print!("{}", text);
+    }
+
     // Export the image to specified file
     pub fn export(&self, path: String) -> std::io::Result<()> {
         // Create a new RGB image
@@ -41,11 +140,8 @@ impl Image {
             let idx: usize = (x + y * self.size.0) as usize;
 
             // Put color into image
-            if let Color::RGB(r, g, b) = self.pixels[idx] {
-                *pixel = image::Rgb([(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8]);
-            } else {
-                panic!("HSV Colors not implemented!");
-            }
+            let (r, g, b) = self.pixels[idx].rgb();
+            *pixel = image::Rgb([(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8]);
         }
 
         if self.scale != 1 {
@@ -1,14 +1,27 @@
 pub mod util;
 
+pub mod buddhabrot;
+pub mod compute;
+pub mod cpu;
+#[cfg(feature = "cuda")]
 pub mod cuda;
+pub mod perturb;
+pub mod series;
 
 pub mod cli;
 pub mod gui;
 
 pub mod colors;
+pub mod doubledouble;
+pub mod floatexp;
+pub mod formula;
 pub mod image;
+pub mod lyapunov;
 pub mod math;
+pub mod newton;
+pub mod real;
 pub mod render;
+pub mod trap;
 
 pub mod http;
 pub mod threadpool;
@@ -0,0 +1,88 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, PartialEq, Debug)]
+// Sequence of A and B that picks which of the pixel's two rates drives each step of the logistic
+// map, x <- r x (1 - x), repeating it as many times as needed
+pub struct Sequence {
+    // Whether each step uses B, rather than A
+    steps: Vec<bool>,
+}
+
+impl Sequence {
+    // Rate r for the given step, where a and b are the pixel's coordinates
+    pub fn rate(&self, step: u32, a: f64, b: f64) -> f64 {
+        if self.steps[step as usize % self.steps.len()] {
+            b
+        } else {
+            a
+        }
+    }
+}
+
+impl Default for Sequence {
+    fn default() -> Sequence {
+        Sequence {
+            steps: vec![false, true],
+        }
+    }
+}
+
+// Allow for parsing sequences from user input, like `AB` or `AABAB`
+impl FromStr for Sequence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let steps = s
+            .trim()
+            .chars()
+            .map(|step| match step {
+                'A' | 'a' => Ok(false),
+                'B' | 'b' => Ok(true),
+                _ => Err(format!("Invalid sequence: {}.", s.trim())),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        if steps.is_empty() {
+            return Err("The sequence needs at least one step.".into());
+        }
+
+        Ok(Sequence { steps })
+    }
+}
+
+impl fmt::Display for Sequence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for step in &self.steps {
+            write!(f, "{}", if *step { 'B' } else { 'A' })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        for s in &["AB", "A", "BBA", "AABAB"] {
+            let sequence: Sequence = s.parse().unwrap();
+            assert_eq!(sequence.to_string(), *s);
+        }
+        assert_eq!(" abba ".parse::<Sequence>().unwrap().to_string(), "ABBA");
+        assert_eq!(Sequence::default().to_string(), "AB");
+
+        for s in &["", "  ", "ABC", "A B"] {
+            assert!(s.parse::<Sequence>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn rates_repeat() {
+        let sequence: Sequence = "AAB".parse().unwrap();
+        let rates: Vec<_> = (0..7).map(|step| sequence.rate(step, 1.0, 2.0)).collect();
+        assert_eq!(rates, [1.0, 1.0, 2.0, 1.0, 1.0, 2.0, 1.0]);
+    }
+}
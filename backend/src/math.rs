@@ -1,105 +1,311 @@
-use std::ops::{Add, Div, Mul, Sub};
+use std::ops::{Add, Div, Mul, Neg, Sub};
 
-// Scalar floating-point type to be used across the program
-pub type Real = f64;
+pub use crate::doubledouble::DoubleDouble;
+pub use crate::floatexp::FloatExp;
+pub use crate::real::Real;
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-// Complex floating-point type to be used across the program
-pub struct Complex(pub Real, pub Real);
+// Operations needed of a scalar type for it to be used in a Complex
+pub trait Float:
+    Clone
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + 'static
+{
+    fn from_f64(value: f64) -> Self;
+    fn from_real(value: &Real) -> Self;
+    fn to_f64(&self) -> f64;
+}
 
-impl Complex {
+impl Float for f64 {
+    fn from_f64(value: f64) -> f64 {
+        value
+    }
+
+    fn from_real(value: &Real) -> f64 {
+        value.to_f64()
+    }
+
+    fn to_f64(&self) -> f64 {
+        *self
+    }
+}
+
+impl Float for Real {
+    fn from_f64(value: f64) -> Real {
+        Real::from(value)
+    }
+
+    fn from_real(value: &Real) -> Real {
+        value.clone()
+    }
+
+    fn to_f64(&self) -> f64 {
+        Real::to_f64(self)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+// Complex floating-point type to be used across the program.  Pixel data is stored with f64
+// components, while the window itself is described with Reals.
+pub struct Complex<T = f64>(pub T, pub T);
+
+// Complex number that can't underflow, for offsets too small for f64s
+pub type ComplexExp = Complex<FloatExp>;
+
+impl<T: Float> Complex<T> {
     // Given the window and image size, get a closure that can be used to convert an image
-    // coordinate to a complex coordinate.
+    // coordinate to a complex coordinate.  The window is converted to the type of the output
+    // only after the scale and shift have been calculated, so no precision is lost there.  If
+    // `flipped` is set, the imaginary axis points down the image rather than up.
     pub fn get_mapping(
         (w, h): (u32, u32),
-        (center, radius): (Complex, Real),
-    ) -> Box<dyn Fn(u32, u32) -> Complex> {
-        // Compute the scale and shift in each dimension
-        let (scale, shift) = if w >= h {
+        (center, radius): (&Complex<Real>, &Real),
+        flipped: bool,
+    ) -> Box<dyn Fn(u32, u32) -> Complex<T>> {
+        let real = |value: u32| Real::from(value as f64);
+        let (center, radius) = (center.clone(), radius.clone());
+
+        // Compute the scale, and the distance from the center to the edges in each dimension
+        let (scale, half) = if w >= h {
             // Radius maps to height
             (
-                2.0 * radius / h as Real,
-                Complex(center.0 - radius * w as Real / h as Real, center.1 + radius),
+                Real::from(2.0) * radius.clone() / real(h),
+                Complex(radius.clone() * real(w) / real(h), radius),
             )
         } else {
             // Radius maps to width
             (
-                2.0 * radius / w as Real,
-                Complex(center.0 - radius, center.1 + radius * h as Real / w as Real),
+                Real::from(2.0) * radius.clone() / real(w),
+                Complex(radius.clone(), radius * real(h) / real(w)),
             )
         };
 
+        // The top left of the image is above the center, unless it's flipped
+        let (shift, scale_y) = if flipped {
+            (Complex(center.0 - half.0, center.1 - half.1), scale.clone())
+        } else {
+            (Complex(center.0 - half.0, center.1 + half.1), -scale.clone())
+        };
+
+        let scale = T::from_real(&scale);
+        let scale_y = T::from_real(&scale_y);
+        let shift = Complex(T::from_real(&shift.0), T::from_real(&shift.1));
+
         // Return the mapping as a boxed closure
         Box::new(move |x: u32, y: u32| {
-            let x = x as Real * scale;
-            let y = y as Real * -scale;
-            Complex(x, y) + shift
+            let x = T::from_f64(x as f64) * scale.clone();
+            let y = T::from_f64(y as f64) * scale_y.clone();
+            Complex(x, y) + shift.clone()
         })
     }
 
     // Compute the squared absolute value of the complex number, which is faster to compute than
     // the actual absolute value because no square root is needed
-    pub fn abs_squared(&self) -> Real {
-        (self.0 * self.0 + self.1 * self.1)
+    pub fn abs_squared(&self) -> T {
+        self.0.clone() * self.0.clone() + self.1.clone() * self.1.clone()
+    }
+
+    // Raise to an integer power by repeated squaring.  Zero to a negative power is taken to be zero,
+    // so that the Mandelbrot set's first iteration still gives c.
+    pub fn powi(&self, n: i32) -> Complex<T> {
+        if n == 2 {
+            return self.clone() * self.clone();
+        }
+
+        let one = Complex(T::from_f64(1.0), T::from_f64(0.0));
+        let zero = T::from_f64(0.0);
+        if n < 0 && self.0 == zero && self.1 == zero {
+            return self.clone();
+        }
+
+        let (mut result, mut base, mut exponent) = (one.clone(), self.clone(), n.unsigned_abs());
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result * base.clone();
+            }
+            base = base.clone() * base;
+            exponent >>= 1;
+        }
+
+        if n < 0 {
+            one / result
+        } else {
+            result
+        }
     }
 
+    // Complex conjugate
+    pub fn conj(&self) -> Complex<T> {
+        Complex(self.0.clone(), -self.1.clone())
+    }
+
+    // Take the absolute value of each component separately
+    pub fn abs_components(&self) -> Complex<T> {
+        Complex(abs(self.0.clone()), abs(self.1.clone()))
+    }
+
+    // Convert from a complex number with Real components
+    pub fn from_real(value: &Complex<Real>) -> Complex<T> {
+        Complex(T::from_real(&value.0), T::from_real(&value.1))
+    }
+
+    // Convert to a complex number with f64 components
+    pub fn to_f64(&self) -> Complex {
+        Complex(self.0.to_f64(), self.1.to_f64())
+    }
+}
+
+impl<T> Complex<T> {
+    // Convert each component to another type
+    pub fn convert<U>(self) -> Complex<U>
+    where
+        T: Into<U>,
+    {
+        Complex(self.0.into(), self.1.into())
+    }
+}
+
+impl Complex<Real> {
+    // The same number, rounded or extended to the given precision
+    pub fn with_precision(&self, precision: u32) -> Complex<Real> {
+        Complex(
+            self.0.with_precision(precision),
+            self.1.with_precision(precision),
+        )
+    }
+}
+
+impl Complex {
     // Compute the absolute value of the complex number
-    pub fn abs(&self) -> Real {
+    pub fn abs(&self) -> f64 {
         self.abs_squared().sqrt()
     }
+
+    // Raise to any real power, using the principal branch.  Zero to any power is taken to be zero.
+    pub fn powf(&self, power: f64) -> Complex {
+        if self.0 == 0.0 && self.1 == 0.0 {
+            return *self;
+        }
+
+        let size = self.abs().powf(power);
+        let angle = self.1.atan2(self.0) * power;
+        Complex(size * angle.cos(), size * angle.sin())
+    }
+
+    // Raise to a power, taking the faster route if it's an integer
+    pub fn pow(&self, power: f64) -> Complex {
+        if is_integer(power) {
+            self.powi(power as i32)
+        } else {
+            self.powf(power)
+        }
+    }
+
+    // Raise to a complex power, using the principal branch.  Real powers go through pow, and zero
+    // to any other power is taken to be zero.
+    pub fn powc(&self, power: Complex) -> Complex {
+        if power.1 == 0.0 {
+            self.pow(power.0)
+        } else if self.0 == 0.0 && self.1 == 0.0 {
+            *self
+        } else {
+            (power * self.log()).exp()
+        }
+    }
+
+    // e to the power of this number
+    pub fn exp(&self) -> Complex {
+        let size = self.0.exp();
+        Complex(size * self.1.cos(), size * self.1.sin())
+    }
+
+    // Natural logarithm, using the principal branch (with the imaginary part in (-pi, pi])
+    pub fn log(&self) -> Complex {
+        Complex(self.abs().ln(), self.1.atan2(self.0))
+    }
+
+    pub fn sin(&self) -> Complex {
+        Complex(self.0.sin() * self.1.cosh(), self.0.cos() * self.1.sinh())
+    }
+
+    pub fn cos(&self) -> Complex {
+        Complex(self.0.cos() * self.1.cosh(), -self.0.sin() * self.1.sinh())
+    }
+}
+
+// Absolute value of any Float
+pub fn abs<T: Float>(value: T) -> T {
+    if value < T::from_f64(0.0) {
+        -value
+    } else {
+        value
+    }
+}
+
+// Whether a power can be used with Complex::powi
+pub fn is_integer(power: f64) -> bool {
+    power.fract() == 0.0 && power.abs() <= i32::MAX as f64
 }
 
 //////////////////////////////////////////////////
 ///////// Operations for Complex Numbers /////////
 //////////////////////////////////////////////////
 
-impl Add for Complex {
-    type Output = Complex;
+impl<T: Float> Add for Complex<T> {
+    type Output = Complex<T>;
 
-    fn add(self, other: Complex) -> Complex {
+    fn add(self, other: Complex<T>) -> Complex<T> {
         Complex(self.0 + other.0, self.1 + other.1)
     }
 }
 
-impl Sub for Complex {
-    type Output = Complex;
+impl<T: Float> Sub for Complex<T> {
+    type Output = Complex<T>;
 
-    fn sub(self, other: Complex) -> Complex {
+    fn sub(self, other: Complex<T>) -> Complex<T> {
         Complex(self.0 - other.0, self.1 - other.1)
     }
 }
 
-impl Mul for Complex {
-    type Output = Complex;
+impl<T: Float> Mul for Complex<T> {
+    type Output = Complex<T>;
 
-    fn mul(self, other: Complex) -> Complex {
+    fn mul(self, other: Complex<T>) -> Complex<T> {
+        let (Complex(a, b), Complex(c, d)) = (self, other);
         Complex(
-            self.0 * other.0 - self.1 * other.1,
-            self.0 * other.1 + self.1 * other.0,
+            a.clone() * c.clone() - b.clone() * d.clone(),
+            a * d + b * c,
         )
     }
 }
 
-impl Div for Complex {
-    type Output = Complex;
+impl<T: Float> Div for Complex<T> {
+    type Output = Complex<T>;
 
-    fn div(self, other: Complex) -> Complex {
+    fn div(self, other: Complex<T>) -> Complex<T> {
         let (Complex(a, b), Complex(c, d)) = (self, other);
-        let denom = c * c + d * d;
-        Complex(a * c + b * d, b * c - a * d) / denom
+        let denom = c.clone() * c.clone() + d.clone() * d.clone();
+        Complex(
+            a.clone() * c.clone() + b.clone() * d.clone(),
+            b * c - a * d,
+        ) / denom
     }
 }
 
-impl Div<Real> for Complex {
-    type Output = Complex;
+impl<T: Float> Div<T> for Complex<T> {
+    type Output = Complex<T>;
 
-    fn div(self, other: Real) -> Complex {
-        Complex(self.0 / other, self.1 / other)
+    fn div(self, other: T) -> Complex<T> {
+        Complex(self.0 / other.clone(), self.1 / other)
     }
 }
 
-impl From<Real> for Complex {
-    fn from(real: Real) -> Complex {
-        Complex(real, 0.0)
+impl<T: Float> From<T> for Complex<T> {
+    fn from(real: T) -> Complex<T> {
+        Complex(real, T::from_f64(0.0))
     }
 }
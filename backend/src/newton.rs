@@ -0,0 +1,126 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::math::Complex;
+
+// A pixel has converged once it's within this distance of a root
+const TOLERANCE: f64 = 1e-6;
+
+#[derive(Clone, PartialEq, Debug)]
+// Polynomial that the Newton fractal finds the roots of, given by its roots, so that
+//   p(z) = (z - r_1)(z - r_2)...(z - r_n)
+pub struct Polynomial {
+    roots: Vec<Complex>,
+}
+
+impl Polynomial {
+    pub fn roots(&self) -> &[Complex] {
+        &self.roots
+    }
+
+    // Take one step of Newton's method, z - p(z)/p'(z).  Since p is a product of its roots,
+    //   p'(z)/p(z) = 1/(z - r_1) + 1/(z - r_2) + ... + 1/(z - r_n)
+    // There's no step from where p'(z) is zero, which gives None.
+    pub fn step(&self, z: Complex) -> Option<Complex> {
+        let one = Complex(1.0, 0.0);
+        let sum = self
+            .roots
+            .iter()
+            .fold(Complex(0.0, 0.0), |sum, root| sum + one / (z - *root));
+
+        if sum.abs_squared() == 0.0 {
+            None
+        } else {
+            Some(z - one / sum)
+        }
+    }
+
+    // Index of the root that z has converged to, if any
+    pub fn root_near(&self, z: Complex) -> Option<u32> {
+        self.roots
+            .iter()
+            .position(|root| (z - *root).abs_squared() < TOLERANCE * TOLERANCE)
+            .map(|idx| idx as u32)
+    }
+}
+
+impl Default for Polynomial {
+    // z^3 - 1, whose roots are the cube roots of unity
+    fn default() -> Polynomial {
+        let (x, y) = (-0.5, 3f64.sqrt() / 2.0);
+        Polynomial {
+            roots: vec![Complex(1.0, 0.0), Complex(x, y), Complex(x, -y)],
+        }
+    }
+}
+
+// Allow for parsing polynomials from user input, as a list of roots separated by semicolons, each
+// given as its real and imaginary parts separated by a comma (or just the real part), like
+// `1;-0.5,0.866;-0.5,-0.866`
+impl FromStr for Polynomial {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let part = |part: &str| match part.trim().parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => Err(format!("Invalid root: {}.", part.trim())),
+        };
+
+        let roots = s
+            .split(';')
+            .map(|root| match root.split_once(',') {
+                Some((x, y)) => Ok(Complex(part(x)?, part(y)?)),
+                None => Ok(Complex(part(root)?, 0.0)),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Polynomial { roots })
+    }
+}
+
+// Polynomials are printed without any whitespace, so that they fit in a single part of a GUI command
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let roots: Vec<_> = self
+            .roots
+            .iter()
+            .map(|root| format!("{},{}", root.0, root.1))
+            .collect();
+
+        write!(f, "{}", roots.join(";"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        for s in &["1,0;-0.5,0.866;-0.5,-0.866", "2,0", "0,1;0,-1;3.25,0"] {
+            let polynomial: Polynomial = s.parse().unwrap();
+            assert_eq!(polynomial.to_string(), *s);
+        }
+
+        // Real roots can leave out their imaginary parts, and whitespace is ignored
+        let polynomial: Polynomial = " 1 ; -2 , 0.5 ".parse().unwrap();
+        assert_eq!(polynomial.roots(), &[Complex(1.0, 0.0), Complex(-2.0, 0.5)]);
+        assert_eq!(polynomial.to_string(), "1,0;-2,0.5");
+
+        for s in &["", "1;", "a", "1,2,3", "inf", "1;nan,0"] {
+            assert!(s.parse::<Polynomial>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn steps() {
+        // z^2 - 1 steps to (z^2 + 1) / 2z, which is 1.25 from 2
+        let polynomial: Polynomial = "1;-1".parse().unwrap();
+        assert_eq!(polynomial.step(Complex(2.0, 0.0)), Some(Complex(1.25, 0.0)));
+        assert_eq!(polynomial.root_near(Complex(-1.0, 1e-7)), Some(1));
+        assert_eq!(polynomial.root_near(Complex(0.0, 0.0)), None);
+
+        // p'(0) = 0, so there's nowhere for z to go
+        assert_eq!(polynomial.step(Complex(0.0, 0.0)), None);
+    }
+}
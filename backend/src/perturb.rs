@@ -0,0 +1,729 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::compute::{ComputeBackend, Result};
+use crate::cpu::for_each_row;
+use crate::math::{is_integer, Complex, ComplexExp, Float, FloatExp, Real};
+use crate::render::{
+    next_derivative, Bailout, FractalKind, Parameters, PixelDetails, Render, RenderError,
+};
+use crate::series::Series;
+use crate::trap::Trap;
+
+// Pauldelbrot's glitch criterion: a pixel has lost too much precision once |z|^2 drops below this
+// fraction of |Z|^2, where Z is the reference orbit at the same iteration
+const GLITCH_TOLERANCE: f64 = 1e-6;
+
+// Most references to use before giving up on any remaining glitched pixels
+const MAX_REFERENCES: usize = 32;
+
+// Number of probe points along each side of the window used to check the series approximation
+const PROBES: u32 = 5;
+
+// Pixel offsets are kept in f64s while the distance between pixels is at least 2^this, which leaves
+// them plenty of room above the smallest normal f64 (2^-1022)
+const MIN_F64_EXPONENT: i64 = -960;
+
+type Pixel = (u32, Complex, Complex, bool);
+
+// A pixel and its details, along with its offset from the reference orbit and, if it's glitched, how
+// badly
+type Item<D> = (Pixel, PixelDetails, Option<Complex<D>>, Option<f64>);
+
+// Type that pixel offsets can be iterated with
+trait Delta: Float + Copy + Send + Sync + From<FloatExp> + Into<FloatExp> {}
+
+impl<D: Float + Copy + Send + Sync + From<FloatExp> + Into<FloatExp>> Delta for D {}
+
+// Backend that computes a single orbit at full precision, and iterates each pixel's (small) offset
+// from it with f64s.  This is only needed when the window is too small for f64s, but works at any
+// zoom.
+pub struct PerturbationBackend;
+
+impl ComputeBackend for PerturbationBackend {
+    fn name(&self) -> &'static str {
+        "perturbation"
+    }
+
+    fn available(&self) -> bool {
+        true
+    }
+
+    // Offsets can only be iterated for whole powers of at least two, and the fractals that take
+    // absolute values only have their offsets worked out for squaring.  Formulas, the Newton
+    // fractal, the Buddhabrot and the Lyapunov fractal aren't supported.
+    fn supports(&self, params: &Parameters) -> bool {
+        if params.formula.is_some() {
+            return false;
+        }
+
+        match params.fractal {
+            FractalKind::Newton
+            | FractalKind::Buddhabrot
+            | FractalKind::AntiBuddhabrot
+            | FractalKind::Lyapunov => false,
+            FractalKind::BurningShip | FractalKind::Celtic | FractalKind::Buffalo => {
+                params.power == 2.0
+            }
+            _ => is_integer(params.power) && params.power >= 2.0,
+        }
+    }
+
+    fn compute(
+        &self,
+        render: Render,
+        progress: Arc<Mutex<Option<f64>>>,
+        cancel: Arc<AtomicBool>,
+    ) -> Result {
+        compute(render, progress, cancel)
+    }
+}
+
+#[derive(Clone)]
+// An orbit computed at full precision, which pixels are iterated relative to
+pub struct Reference {
+    // Where the reference is in the window, which is its c, or for the Julia set its starting z
+    pub point: Complex<Real>,
+    pub c: Complex<Real>,
+    pub fractal: FractalKind,
+    pub power: i32,
+    // Bailout and escape radius that the orbit diverges past
+    pub escape: (Bailout, f64),
+    // The orbit so far, rounded to f64s
+    pub orbit: Vec<Complex>,
+    // The last value of the orbit at full precision, so that it can be extended
+    z: Complex<Real>,
+}
+
+impl Reference {
+    // Start an orbit at the given point, using the Julia set's constant if there is one
+    pub fn new(
+        point: Complex<Real>,
+        julia: Option<&Complex<Real>>,
+        fractal: FractalKind,
+        power: i32,
+        escape: (Bailout, f64),
+    ) -> Reference {
+        let precision = point.0.precision();
+        let (c, z) = match julia {
+            Some(c) => (c.clone(), point.clone()),
+            None => (
+                point.clone(),
+                Complex(Real::zero(precision), Real::zero(precision)),
+            ),
+        };
+
+        Reference {
+            point,
+            c,
+            fractal,
+            power,
+            escape,
+            orbit: vec![z.to_f64()],
+            z,
+        }
+    }
+
+    // Whether the orbit has diverged, after which it can't be extended any further
+    pub fn diverged(&self) -> bool {
+        self.escape
+            .0
+            .escaped(self.orbit.last().unwrap(), self.escape.1)
+    }
+
+    // Extend the orbit up to the given number of iterations, or until it diverges.  Returns false if
+    // the render was cancelled part way through.
+    fn extend(&mut self, iterations: u32, cancel: &AtomicBool) -> bool {
+        while self.orbit.len() <= iterations as usize && !self.diverged() {
+            if self.orbit.len().is_multiple_of(1000) && cancel.load(Ordering::Relaxed) {
+                return false;
+            }
+
+            let power = self.power;
+            self.z = self.fractal.pow(&self.z, |z| z.powi(power)) + self.c.clone();
+            self.orbit.push(self.z.to_f64());
+        }
+
+        true
+    }
+}
+
+#[derive(Clone)]
+// What's needed to pick a perturbation render back up where it left off
+pub struct Perturbation {
+    pub reference: Reference,
+    // Number of iterations the render had been computed to
+    pub iterations: u32,
+    // Each pixel's offset from the reference orbit, or None if it has to start over from iteration
+    // zero (because it hasn't started yet, or it was computed relative to another reference)
+    pub deltas: Vec<Option<ComplexExp>>,
+}
+
+pub fn compute(
+    mut render: Render,
+    progress: Arc<Mutex<Option<f64>>>,
+    cancel: Arc<AtomicBool>,
+) -> Result {
+    let (width, height) = render.params.image_size;
+    let iterations = render.params.max_iter;
+    let power = render.params.power as i32;
+    let escape = (render.params.bailout, render.params.escape_radius);
+
+    let precision = render.params.precision();
+    let center = render.params.center.with_precision(precision);
+    let radius = render.params.radius.with_precision(precision);
+    let julia = render
+        .params
+        .julia_constant()
+        .map(|c| c.with_precision(precision));
+
+    // Pick up from the previous state if it's still valid, otherwise start over around the center
+    let (mut reference, deltas) = match render.perturbation.take() {
+        Some(state) if state.iterations == render.iterations => (state.reference, state.deltas),
+        _ => (
+            Reference::new(
+                center.clone(),
+                julia.as_ref(),
+                render.params.fractal,
+                power,
+                escape,
+            ),
+            vec![None; render.pixels.len()],
+        ),
+    };
+
+    if !reference.extend(iterations, &cancel) {
+        return Err(RenderError("Render cancelled.".into()));
+    }
+
+    // Offsets are kept in f64s while they're comfortably above the smallest normal f64, and in
+    // FloatExps (which are slower) beyond that
+    let step = Real::from(2.0) * radius / Real::from(std::cmp::min(width, height) as f64);
+    let pixels = if step.exponent() < MIN_F64_EXPONENT {
+        perturb_all::<FloatExp>(
+            render.pixels,
+            render.details,
+            deltas,
+            &reference,
+            &progress,
+            &cancel,
+            (&render.params, render.stride),
+        )?
+    } else {
+        perturb_all::<f64>(
+            render.pixels,
+            render.details,
+            deltas,
+            &reference,
+            &progress,
+            &cancel,
+            (&render.params, render.stride),
+        )?
+    };
+
+    let mut details = Vec::with_capacity(pixels.len());
+    let (pixels, deltas) = pixels
+        .into_iter()
+        .map(|(pixel, pixel_details, delta)| {
+            details.push(pixel_details);
+            (pixel, delta)
+        })
+        .unzip();
+
+    Ok(Render {
+        iterations,
+        pixels,
+        details,
+        perturbation: Some(Perturbation {
+            reference,
+            iterations,
+            deltas,
+        }),
+        ..render
+    })
+}
+
+// Iterate every pixel on the stride relative to the main reference, then keep adding references for
+// the pixels that glitched, storing offsets with the given type along the way
+fn perturb_all<D: Delta>(
+    pixels: Vec<Pixel>,
+    details: Vec<PixelDetails>,
+    deltas: Vec<Option<ComplexExp>>,
+    reference: &Reference,
+    progress: &Arc<Mutex<Option<f64>>>,
+    cancel: &Arc<AtomicBool>,
+    (params, stride): (&Parameters, u32),
+) -> std::result::Result<Vec<(Pixel, PixelDetails, Option<ComplexExp>)>, RenderError> {
+    let (width, height) = params.image_size;
+    let iterations = params.max_iter;
+
+    let precision = params.precision();
+    let center = params.center.with_precision(precision);
+    let radius = params.radius.with_precision(precision);
+    let julia = params.julia_constant().map(|c| c.with_precision(precision));
+    let stripe_density = if params.averages {
+        Some(params.stripe_density)
+    } else {
+        None
+    };
+
+    // Each pixel is offset from the top left by a multiple of the pixel spacing, so its offset from
+    // the reference can be found without going back to full precision
+    let mapping = Complex::<Real>::get_mapping(
+        (width, height),
+        (&center, &radius),
+        params.fractal.flipped(),
+    );
+    let step = Complex(
+        mapping(1, 0).0 - mapping(0, 0).0,
+        mapping(0, 1).1 - mapping(0, 0).1,
+    );
+    let offset = |c: &Complex<Real>| {
+        let origin = mapping(0, 0) - c.clone();
+        Complex(D::from_real(&origin.0), D::from_real(&origin.1))
+    };
+
+    let items: Vec<Item<D>> = pixels
+        .into_iter()
+        .zip(details)
+        .zip(deltas)
+        .map(|((pixel, details), delta)| (pixel, details, delta.map(|delta| delta.convert()), None))
+        .collect();
+
+    // Skip as many of the early iterations as the series approximation allows, checking it
+    // against probe points spread evenly over the window (including its corners).  The series
+    // coefficients grow far beyond what an f64 can hold, so it always works in FloatExps.  The
+    // series is only worked out for squaring z (without folding it), so other powers and fractals
+    // iterate every pixel from the start.  So do renders that keep averages, since every term of an
+    // average counts, and the reference's own terms are too far off for pixels away from it.
+    let origin = offset(&reference.point).convert::<FloatExp>();
+    let step_exp = Complex::<FloatExp>::from_real(&step);
+    let probes: Vec<ComplexExp> = (0..PROBES * PROBES)
+        .map(|p| {
+            let x = (p % PROBES) as f64 / (PROBES - 1) as f64 * (width - 1) as f64;
+            let y = (p / PROBES) as f64 / (PROBES - 1) as f64 * (height - 1) as f64;
+            origin
+                + Complex(
+                    FloatExp::from(x) * step_exp.0,
+                    FloatExp::from(y) * step_exp.1,
+                )
+        })
+        .collect();
+    let analytic = matches!(
+        reference.fractal,
+        FractalKind::Mandelbrot | FractalKind::Julia
+    );
+    let series = if analytic && reference.power == 2 && !params.averages {
+        let series = Series::new(
+            &reference.orbit,
+            &probes,
+            iterations,
+            julia.is_some(),
+            reference.escape,
+        );
+        Some(Arc::new(series))
+    } else {
+        None
+    };
+
+    let mut pass = Pass {
+        orbit: Arc::new(reference.orbit.clone()),
+        origin: offset(&reference.point),
+        step: Complex::from_real(&step),
+        series,
+        julia: julia.is_some(),
+        fractal: reference.fractal,
+        power: reference.power,
+        escape: reference.escape,
+        stats: params.interior_stats,
+        trap: params.trap.clone(),
+        averages: stripe_density,
+        skipped: PixelDetails::default(),
+        stride,
+        glitched_only: false,
+    };
+
+    // Pixels skipping ahead with the series stay close to the reference, so their stats and closest
+    // approach to the trap over the skipped iterations are taken to be the reference's
+    if let Some(series) = &pass.series {
+        let (orbit, c) = (&reference.orbit, reference.c.to_f64());
+        let mut skipped = PixelDetails::default();
+        for n in 1..series.iterations as usize {
+            pass.record(&mut skipped, (orbit[n - 1], orbit[n], c), n as u32, false);
+        }
+        pass.skipped = skipped;
+    }
+    let mut items = perturb(items, width, Some(progress), cancel, pass, iterations)?;
+
+    // Start again with a new reference for any pixels that glitched
+    for _ in 1..MAX_REFERENCES {
+        // Use the pixel that lost the most precision, which is usually near the middle of a glitch
+        let glitched = items
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, (_, _, _, glitch))| glitch.map(|glitch| (idx, glitch)))
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        let idx = match glitched {
+            Some((idx, _)) => idx as u32,
+            None => break,
+        };
+
+        let mut secondary = Reference::new(
+            mapping(idx % width, idx / width),
+            julia.as_ref(),
+            reference.fractal,
+            reference.power,
+            reference.escape,
+        );
+        if !secondary.extend(iterations, cancel) {
+            return Err(RenderError("Render cancelled.".into()));
+        }
+
+        let pass = Pass {
+            origin: offset(&secondary.point),
+            orbit: Arc::new(secondary.orbit),
+            step: Complex::from_real(&step),
+            series: None,
+            julia: julia.is_some(),
+            fractal: reference.fractal,
+            power: reference.power,
+            escape: reference.escape,
+            stats: params.interior_stats,
+            trap: params.trap.clone(),
+            averages: stripe_density,
+            skipped: PixelDetails::default(),
+            stride,
+            glitched_only: true,
+        };
+        items = perturb(items, width, None, cancel, pass, iterations)?;
+    }
+
+    if cancel.load(Ordering::Relaxed) {
+        return Err(RenderError("Render cancelled.".into()));
+    }
+
+    Ok(items
+        .into_iter()
+        .map(|(pixel, details, delta, _)| (pixel, details, delta.map(|delta| delta.convert())))
+        .collect())
+}
+
+// A pass over the pixels, relative to one reference orbit
+struct Pass<D> {
+    orbit: Arc<Vec<Complex>>,
+    // The top left pixel is `origin` away from the reference, and pixels are `step.0` apart along
+    // the real axis and `step.1` apart along the imaginary axis
+    origin: Complex<D>,
+    step: Complex<D>,
+    // Series approximation for pixels starting from the beginning to skip ahead with, if any
+    series: Option<Arc<Series<FloatExp>>>,
+    // Whether the offsets are in the starting z (for the Julia set) rather than in c
+    julia: bool,
+    fractal: FractalKind,
+    power: i32,
+    escape: (Bailout, f64),
+    // Whether to keep track of the smallest |z|, the trap to keep track of the closest approach to
+    // and the stripe density for the averages, if any, and all of those over the iterations the
+    // series skips (other than the last)
+    stats: bool,
+    trap: Option<Trap>,
+    averages: Option<f64>,
+    skipped: PixelDetails,
+    // Only the pixels on every stride-th column and row are iterated
+    stride: u32,
+    // Whether to only iterate the glitched pixels (from the beginning)
+    glitched_only: bool,
+}
+
+impl<D> Pass<D> {
+    // Take the step from `previous` to z, with the constant c, into account for whichever of the
+    // stats, closest approach to the trap and averages the render keeps
+    fn record(
+        &self,
+        details: &mut PixelDetails,
+        (previous, z, c): (Complex, Complex, Complex),
+        i: u32,
+        escaped: bool,
+    ) {
+        if self.stats {
+            details.record_minimum(z.abs(), i, escaped);
+        }
+        if let Some(trap) = &self.trap {
+            details.record_trap(trap, z);
+        }
+        if let Some(density) = self.averages {
+            let power = Some(self.power as f64);
+            details.record_averages((previous, z, c), i, power, density);
+        }
+    }
+}
+
+// Iterate the pixels relative to the given pass's reference orbit
+fn perturb<D: Delta>(
+    items: Vec<Item<D>>,
+    width: u32,
+    progress: Option<&Mutex<Option<f64>>>,
+    cancel: &Arc<AtomicBool>,
+    pass: Pass<D>,
+    iterations: u32,
+) -> std::result::Result<Vec<Item<D>>, RenderError> {
+    let cancel = Arc::clone(cancel);
+    let zero = Complex(D::from_f64(0.0), D::from_f64(0.0));
+
+    for_each_row(items, width, progress, move |y, row| {
+        let Pass {
+            origin,
+            step,
+            ref series,
+            julia,
+            stride,
+            glitched_only,
+            ..
+        } = pass;
+
+        for (x, (pixel, details, delta, glitch)) in row.iter_mut().enumerate() {
+            // Leave the rest of the row alone if the render has been cancelled
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+
+            // Pixels off the stride are left for a later pass
+            if !(x as u32).is_multiple_of(stride) || !(y as u32).is_multiple_of(stride) {
+                continue;
+            }
+
+            // Pixels that have already reached the max iterations (including those proven to be
+            // inside the set up front) are finished too
+            if pixel.3 || pixel.0 >= iterations || (glitched_only && glitch.is_none()) {
+                continue;
+            }
+
+            let offset = origin
+                + Complex(
+                    D::from_f64(x as f64) * step.0,
+                    D::from_f64(y as f64) * step.1,
+                );
+
+            // For the Julia set, pixels only differ in where they start
+            let (dz0, dc) = if julia {
+                (offset, zero)
+            } else {
+                (zero, offset)
+            };
+
+            // Pick up from the previous offset if there is one, otherwise start over, skipping
+            // ahead with the series approximation if there is one.  The derivative of the series
+            // is the derivative of z after the skipped iterations.
+            let mut dz = match (*delta, series) {
+                (Some(dz), _) if !glitched_only => dz,
+                (_, Some(series)) if series.iterations > 0 => {
+                    let dz = series.evaluate(&offset.convert()).convert();
+                    pixel.0 = series.iterations;
+                    pixel.2 = pass.orbit[pixel.0 as usize] + dz.to_f64();
+                    pixel.3 = pass.escape.0.escaped(&pixel.2, pass.escape.1);
+                    details.derivative = series.derivative(&offset.convert()).to_f64();
+                    details.minimum = pass.skipped.minimum;
+                    details.trap = pass.skipped.trap;
+                    details.stripe = pass.skipped.stripe;
+                    details.triangle = pass.skipped.triangle;
+                    let previous = pass.orbit[pixel.0 as usize - 1];
+                    pass.record(details, (previous, pixel.2, pixel.1), pixel.0, pixel.3);
+                    dz
+                }
+                _ => {
+                    pixel.0 = 0;
+                    details.derivative = Complex(if julia { 1.0 } else { 0.0 }, 0.0);
+                    details.minimum = None;
+                    details.trap = None;
+                    details.stripe = None;
+                    details.triangle = None;
+                    dz0
+                }
+            };
+
+            *glitch = iterate(pixel, (&mut dz, details), dc, &pass, iterations);
+
+            // The offset is only any use later on if it's relative to the main reference
+            *delta = if glitched_only || glitch.is_some() {
+                None
+            } else {
+                Some(dz)
+            };
+        }
+    })
+}
+
+// Iterate a single pixel's offset dz from the reference orbit Z, using
+// (Z + dz)^2 + (C + dc) - (Z^2 + C) = 2 Z dz + dz^2 + dc, or the equivalent for other powers and
+// fractals.  Returns how badly the pixel glitched, if it did.
+fn iterate<D: Delta>(
+    pixel: &mut Pixel,
+    (dz, details): (&mut Complex<D>, &mut PixelDetails),
+    dc: Complex<D>,
+    pass: &Pass<D>,
+    iterations: u32,
+) -> Option<f64> {
+    let (ref mut i, c, ref mut z, ref mut d) = *pixel;
+    let (orbit, fractal, power) = (&pass.orbit, pass.fractal, pass.power);
+
+    // Binomial coefficients C(n, k) for k = 0..=n
+    let mut binomials = vec![1.0; power as usize + 1];
+    for k in 1..power as usize {
+        binomials[k] = binomials[k - 1] * (power as usize + 1 - k) as f64 / k as f64;
+    }
+
+    while *i < iterations && !*d {
+        // The reference diverged before this pixel did, so it can't go any further with it
+        if *i as usize + 1 >= orbit.len() {
+            return Some(1.0);
+        }
+
+        let reference = orbit[*i as usize];
+        let previous = reference + dz.to_f64();
+        details.derivative =
+            next_derivative(previous, details.derivative, power as f64, pass.julia);
+
+        let reference = Complex(D::from_f64(reference.0), D::from_f64(reference.1));
+        *dz = perturbed_pow(fractal, reference, *dz, &binomials) + dc;
+        *i += 1;
+
+        let reference = orbit[*i as usize];
+        *z = reference + dz.to_f64();
+
+        // Check to see if it's diverged or glitched
+        let size = z.abs_squared();
+        let escaped = pass.escape.0.escaped(z, pass.escape.1);
+        pass.record(details, (previous, *z, c), *i, escaped);
+
+        if escaped {
+            *d = true;
+        } else if size < reference.abs_squared() * GLITCH_TOLERANCE {
+            return Some(size / reference.abs_squared());
+        }
+    }
+
+    None
+}
+
+// Work out how much raising z to the power (and folding it) moves the offset, f(Z + dz) - f(Z),
+// given the binomial coefficients for the power
+fn perturbed_pow<D: Delta>(
+    fractal: FractalKind,
+    reference: Complex<D>,
+    dz: Complex<D>,
+    binomials: &[f64],
+) -> Complex<D> {
+    let squaring = binomials.len() == 3;
+
+    match fractal {
+        FractalKind::Mandelbrot | FractalKind::Julia if squaring => {
+            (reference + reference) * dz + dz * dz
+        }
+        FractalKind::Mandelbrot | FractalKind::Julia => binomial(reference, dz, binomials),
+        FractalKind::Tricorn if squaring => {
+            let (reference, dz) = (reference.conj(), dz.conj());
+            (reference + reference) * dz + dz * dz
+        }
+        FractalKind::Tricorn => binomial(reference.conj(), dz.conj(), binomials),
+        // The rest only support squaring, where the real part of (Z + dz)^2 - Z^2 is
+        // (2X + a) a - (2Y + b) b, and the imaginary part is twice (X + a)(Y + b) - XY
+        _ => {
+            let (Complex(x, y), Complex(a, b)) = (reference, dz);
+            let two = D::from_f64(2.0);
+            let real = (x + x + a) * a - (y + y + b) * b;
+            let imag = x * b + a * y + a * b;
+
+            match fractal {
+                FractalKind::BurningShip => Complex(real, two * diffabs(x * y, imag)),
+                FractalKind::Celtic => Complex(diffabs(x * x - y * y, real), two * imag),
+                _ => Complex(diffabs(x * x - y * y, real), two * diffabs(x * y, imag)),
+            }
+        }
+    }
+}
+
+// Work out |c + d| - |c| without cancellation, for when d is much smaller than c
+fn diffabs<D: Delta>(c: D, d: D) -> D {
+    let zero = D::from_f64(0.0);
+
+    if c >= zero {
+        if c + d >= zero {
+            d
+        } else {
+            -(c + c + d)
+        }
+    } else if c + d > zero {
+        c + c + d
+    } else {
+        -d
+    }
+}
+
+// Work out (Z + dz)^n - Z^n = sum(C(n, k) Z^(n-k) dz^k, k = 1..n) without cancellation, given the
+// binomial coefficients C(n, k)
+fn binomial<D: Delta>(reference: Complex<D>, dz: Complex<D>, binomials: &[f64]) -> Complex<D> {
+    let n = binomials.len() - 1;
+
+    // Powers of Z from Z^0 up to Z^(n-1)
+    let mut powers = Vec::with_capacity(n);
+    powers.push(Complex::from(D::from_f64(1.0)));
+    for k in 1..n {
+        powers.push(powers[k - 1] * reference);
+    }
+
+    // Horner's method in dz, with an extra factor of dz since there's no constant term
+    let mut sum = Complex::from(D::from_f64(0.0));
+    for k in (1..=n).rev() {
+        sum = sum * dz + Complex::from(D::from_f64(binomials[k])) * powers[n - k];
+    }
+    sum * dz
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CpuBackend;
+
+    // Render the window all the way through with the given backend
+    fn render(params: &Parameters, backend: &dyn ComputeBackend) -> Render {
+        let progress = Arc::new(Mutex::new(None));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let render = Render::new(params.clone());
+        backend.compute(render, progress, cancel).unwrap()
+    }
+
+    #[test]
+    fn matches_the_cpu_backend() {
+        // Near the dendrite at the top of the set, where the escape counts vary a lot across the
+        // window, while the CPU backend can still tell the pixels apart with f64s
+        let params = Parameters {
+            image_size: (64, 64),
+            center: Complex(
+                "-0.10109636384562".parse().unwrap(),
+                "0.95628651080914".parse().unwrap(),
+            ),
+            radius: "1e-10".parse().unwrap(),
+            max_iter: 500,
+            subdivision: false,
+            ..Parameters::default()
+        };
+        let exact = render(&params, &CpuBackend);
+        let perturbed = render(&params, &PerturbationBackend);
+        assert!(perturbed.perturbation.is_some());
+        assert!(exact
+            .pixels
+            .iter()
+            .any(|pixel| pixel.0 > exact.pixels[0].0 + 50));
+
+        // Rounding in the f64s, and any pixels left glitched after the last reference, can throw a
+        // few pixels off, but there should be hardly any
+        let matching = exact
+            .pixels
+            .iter()
+            .zip(&perturbed.pixels)
+            .filter(|(a, b)| a.0 == b.0 && a.3 == b.3)
+            .count();
+        assert!(matching * 100 >= exact.pixels.len() * 99);
+    }
+}
@@ -0,0 +1,967 @@
+use std::cmp::{self, Ordering};
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::FromStr;
+
+// Number of bits in each limb of the mantissa
+const LIMB_BITS: u64 = 32;
+
+// Precision (in bits) used for numbers that don't ask for anything more
+pub const DEFAULT_PRECISION: u32 = 64;
+
+const LOG2_10: f64 = std::f64::consts::LOG2_10;
+const LOG10_2: f64 = std::f64::consts::LOG10_2;
+
+// Largest decimal exponent accepted when parsing, which keeps the binary exponent of anything
+// computed from a parsed number far from overflowing
+const MAX_EXP10: i64 = 1_000_000_000;
+
+#[derive(Clone, Debug)]
+// Multiple-precision floating-point number.  The precision is chosen per value (rounded up to a
+// whole number of 32-bit limbs), and the result of an operation has the precision of its most
+// precise operand.  The exponent is a 64-bit integer, so there is no practical limit on how small
+// or large a number can be.
+pub struct Real {
+    negative: bool,
+    // Mantissa, least significant limb first.  It is normalised so that the top bit of the last
+    // limb is set, unless the number is zero, in which case every limb is zero.
+    mantissa: Vec<u32>,
+    // The number is mantissa * 2^(exponent - 32 * limbs), so it lies in [2^(exponent-1), 2^exponent)
+    exponent: i64,
+}
+
+impl Real {
+    // Zero, with the given precision
+    pub fn zero(precision: u32) -> Real {
+        Real {
+            negative: false,
+            mantissa: vec![0; limbs_for(precision)],
+            exponent: 0,
+        }
+    }
+
+    // Number of bits in the mantissa
+    pub fn precision(&self) -> u32 {
+        (self.mantissa.len() as u64 * LIMB_BITS) as u32
+    }
+
+    // The same number, rounded or extended to the given precision
+    pub fn with_precision(&self, precision: u32) -> Real {
+        Real::from_parts(
+            self.negative,
+            &self.mantissa,
+            self.scale(),
+            limbs_for(precision),
+        )
+    }
+
+    // Binary exponent of the number, such that its absolute value lies in
+    // [2^(exponent-1), 2^exponent).  Zero has an exponent of zero.
+    pub fn exponent(&self) -> i64 {
+        self.exponent
+    }
+
+    pub fn is_zero(&self) -> bool {
+        *self.mantissa.last().unwrap() == 0
+    }
+
+    pub fn is_sign_negative(&self) -> bool {
+        self.negative
+    }
+
+    // Absolute value
+    pub fn abs(&self) -> Real {
+        Real {
+            negative: false,
+            ..self.clone()
+        }
+    }
+
+    // Convert to the nearest f64, which may overflow to infinity or underflow to zero
+    pub fn to_f64(&self) -> f64 {
+        let (mantissa, exponent) = self.to_f64_exp();
+        ldexp(mantissa, exponent)
+    }
+
+    // Split into an f64 mantissa (with an absolute value in [0.5, 1), or zero) and a binary
+    // exponent, which unlike to_f64 can't overflow or underflow
+    pub fn to_f64_exp(&self) -> (f64, i64) {
+        if self.is_zero() {
+            return (0.0, 0);
+        }
+
+        // The top 64 bits of the mantissa are more than enough to fill an f64
+        let n = self.mantissa.len();
+        let hi = self.mantissa[n - 1] as u64;
+        let lo = if n > 1 {
+            self.mantissa[n - 2] as u64
+        } else {
+            0
+        };
+        let top = ((hi << 32) | lo) as f64 * 2f64.powi(-64);
+
+        // Rounding can carry all the way up to 1
+        let (top, exponent) = if top >= 1.0 {
+            (top / 2.0, self.exponent + 1)
+        } else {
+            (top, self.exponent)
+        };
+
+        if self.negative {
+            (-top, exponent)
+        } else {
+            (top, exponent)
+        }
+    }
+
+    // Exponent of the least significant bit of the mantissa
+    fn scale(&self) -> i64 {
+        self.exponent - self.mantissa.len() as i64 * LIMB_BITS as i64
+    }
+
+    // Build a number from an unsigned integer (as limbs) multiplied by 2^scale, rounding it to the
+    // given number of limbs
+    fn from_parts(negative: bool, int: &[u32], scale: i64, limbs: usize) -> Real {
+        let len = bit_len(int);
+        if len == 0 {
+            return Real {
+                negative: false,
+                mantissa: vec![0; limbs],
+                exponent: 0,
+            };
+        }
+
+        let target = limbs as u64 * LIMB_BITS;
+        let mut exponent = scale + len as i64;
+
+        let mut mantissa = if len > target {
+            // Too many bits, so shift the extra ones out and round to nearest
+            let shift = len - target;
+            let mut mantissa = shr(int, shift);
+            if bit(int, shift - 1) {
+                mantissa = add_mag(&mantissa, &[1]);
+
+                // Rounding up may carry into a new bit
+                if bit_len(&mantissa) > target {
+                    mantissa = shr(&mantissa, 1);
+                    exponent += 1;
+                }
+            }
+            mantissa
+        } else {
+            shl(int, target - len)
+        };
+
+        // The shifts may leave zero limbs above the top one
+        mantissa.resize(limbs, 0);
+
+        Real {
+            negative,
+            mantissa,
+            exponent,
+        }
+    }
+
+    // Parse a decimal string into a number of the given precision
+    pub fn parse(s: &str, precision: u32) -> Result<Real, String> {
+        let decimal = Decimal::parse(s)?;
+        Ok(decimal.to_real(precision))
+    }
+
+    // Compute 10^power to the given number of limbs, by repeated squaring
+    fn pow10(power: u64, limbs: usize) -> Real {
+        let mut result = Real::from_parts(false, &[1], 0, limbs);
+        let mut base = Real::from_parts(false, &[10], 0, limbs);
+        let mut power = power;
+
+        while power > 0 {
+            if power & 1 == 1 {
+                result = result * base.clone();
+            }
+            base = base.clone() * base;
+            power >>= 1;
+        }
+
+        result
+    }
+
+    // Format as a decimal, rounded to the given number of significant digits
+    pub fn to_string_with_digits(&self, digits: usize) -> String {
+        if self.is_zero() {
+            return "0".into();
+        }
+
+        let (string, exp10) = self.to_digits(digits);
+        format_digits(self.negative, &string, exp10)
+    }
+
+    // Round the absolute value to `digits` significant decimal digits, returning the digits and the
+    // decimal exponent of the first one
+    fn to_digits(&self, digits: usize) -> (String, i64) {
+        let limbs = self.mantissa.len() + 2;
+
+        // Estimate the decimal exponent from the binary one and the top of the mantissa
+        let top = *self.mantissa.last().unwrap() as f64 / 2f64.powi(32);
+        let mut exp10 = ((self.exponent as f64 + top.log2()) * LOG10_2).floor() as i64;
+
+        loop {
+            // Scale so that the integer part has exactly `digits` digits
+            let shift = digits as i64 - 1 - exp10;
+            let power = Real::pow10(shift.unsigned_abs(), limbs);
+            let scaled = if shift >= 0 {
+                self.abs().with_precision(limbs as u32 * 32) * power
+            } else {
+                self.abs().with_precision(limbs as u32 * 32) / power
+            };
+
+            let string = to_decimal_string(&scaled.round_to_integer());
+
+            // The estimate of the exponent may be off by one, in which case try again
+            if string.len() > digits {
+                exp10 += 1;
+            } else if string.len() < digits {
+                exp10 -= 1;
+            } else {
+                return (string, exp10);
+            }
+        }
+    }
+
+    // Round a non-negative number to the nearest integer, returned as limbs
+    fn round_to_integer(&self) -> Vec<u32> {
+        let scale = self.scale();
+        if scale >= 0 {
+            shl(&self.mantissa, scale as u64)
+        } else if (-scale) as u64 > self.mantissa.len() as u64 * LIMB_BITS {
+            vec![0]
+        } else {
+            let shift = (-scale) as u64;
+            let int = shr(&self.mantissa, shift);
+            if bit(&self.mantissa, shift - 1) {
+                add_mag(&int, &[1])
+            } else {
+                int
+            }
+        }
+    }
+
+    // Compare absolute values
+    fn cmp_abs(&self, other: &Real) -> Ordering {
+        match (self.is_zero(), other.is_zero()) {
+            (true, true) => return Ordering::Equal,
+            (true, false) => return Ordering::Less,
+            (false, true) => return Ordering::Greater,
+            _ => (),
+        }
+
+        if self.exponent != other.exponent {
+            return self.exponent.cmp(&other.exponent);
+        }
+
+        // Same exponent, so compare the mantissas from the top down, treating the shorter one
+        // as though it were padded with zeros
+        let (a, b) = (&self.mantissa, &other.mantissa);
+        for i in 0..cmp::max(a.len(), b.len()) {
+            let x = if i < a.len() { a[a.len() - 1 - i] } else { 0 };
+            let y = if i < b.len() { b[b.len() - 1 - i] } else { 0 };
+            if x != y {
+                return x.cmp(&y);
+            }
+        }
+
+        Ordering::Equal
+    }
+}
+
+//////////////////////////////////////////////////
+////////// Operations for Real Numbers ///////////
+//////////////////////////////////////////////////
+
+impl Add for Real {
+    type Output = Real;
+
+    fn add(self, other: Real) -> Real {
+        let limbs = cmp::max(self.mantissa.len(), other.mantissa.len());
+
+        if other.is_zero() {
+            return self.with_precision(limbs as u32 * 32);
+        } else if self.is_zero() {
+            return other.with_precision(limbs as u32 * 32);
+        }
+
+        // Line both mantissas up in an integer with a couple of extra limbs, scaled to fit the
+        // larger of the two
+        let (big, small) = if self.exponent >= other.exponent {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let work = (limbs as u64 + 2) * LIMB_BITS;
+        let scale = big.exponent - work as i64;
+
+        let a = shl(&big.mantissa, work - big.mantissa.len() as u64 * LIMB_BITS);
+        let shift = small.scale() - scale;
+        let b = if shift >= 0 {
+            shl(&small.mantissa, shift as u64)
+        } else {
+            shr(&small.mantissa, (-shift) as u64)
+        };
+
+        if big.negative == small.negative {
+            Real::from_parts(big.negative, &add_mag(&a, &b), scale, limbs)
+        } else {
+            match cmp_mag(&a, &b) {
+                Ordering::Equal => Real::zero(limbs as u32 * 32),
+                Ordering::Greater => Real::from_parts(big.negative, &sub_mag(&a, &b), scale, limbs),
+                Ordering::Less => Real::from_parts(small.negative, &sub_mag(&b, &a), scale, limbs),
+            }
+        }
+    }
+}
+
+impl Sub for Real {
+    type Output = Real;
+
+    fn sub(self, other: Real) -> Real {
+        self + -other
+    }
+}
+
+impl Mul for Real {
+    type Output = Real;
+
+    fn mul(self, other: Real) -> Real {
+        let limbs = cmp::max(self.mantissa.len(), other.mantissa.len());
+        Real::from_parts(
+            self.negative != other.negative,
+            &mul_mag(&self.mantissa, &other.mantissa),
+            self.scale() + other.scale(),
+            limbs,
+        )
+    }
+}
+
+impl Div for Real {
+    type Output = Real;
+
+    fn div(self, other: Real) -> Real {
+        assert!(!other.is_zero(), "Division of a Real by zero");
+
+        let limbs = cmp::max(self.mantissa.len(), other.mantissa.len());
+
+        // Shift the dividend up far enough that the integer quotient has a limb to spare
+        let (a, b) = (&self.mantissa, &other.mantissa);
+        let shift = (limbs as u64 + 1) * LIMB_BITS + b.len() as u64 * LIMB_BITS;
+        let quotient = div_mag(&shl(a, shift), b);
+
+        Real::from_parts(
+            self.negative != other.negative,
+            &quotient,
+            self.scale() - other.scale() - shift as i64,
+            limbs,
+        )
+    }
+}
+
+impl Neg for Real {
+    type Output = Real;
+
+    fn neg(self) -> Real {
+        Real {
+            negative: !self.negative && !self.is_zero(),
+            ..self
+        }
+    }
+}
+
+impl PartialEq for Real {
+    fn eq(&self, other: &Real) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Real {
+    fn partial_cmp(&self, other: &Real) -> Option<Ordering> {
+        let ordering = match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.cmp_abs(other),
+            (true, true) => other.cmp_abs(self),
+        };
+        Some(ordering)
+    }
+}
+
+impl From<f64> for Real {
+    fn from(value: f64) -> Real {
+        assert!(value.is_finite(), "Can't convert {} to a Real", value);
+
+        let bits = value.to_bits();
+        let negative = bits >> 63 == 1;
+        let exponent = ((bits >> 52) & 0x7ff) as i64;
+        let fraction = bits & 0xf_ffff_ffff_ffff;
+
+        // Normal numbers have an implicit leading 1, subnormals don't
+        let (int, scale) = if exponent == 0 {
+            (fraction, -1074)
+        } else {
+            (fraction | 1 << 52, exponent - 1075)
+        };
+
+        Real::from_parts(
+            negative,
+            &[int as u32, (int >> 32) as u32],
+            scale,
+            limbs_for(DEFAULT_PRECISION),
+        )
+    }
+}
+
+// Allow for parsing from user input.  The precision is chosen so that every digit given is kept.
+impl FromStr for Real {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let decimal = Decimal::parse(s)?;
+        let bits = (decimal.digits as f64 * LOG2_10).ceil() as u32 + 16;
+        Ok(decimal.to_real(cmp::max(bits, DEFAULT_PRECISION)))
+    }
+}
+
+// Print the shortest decimal that parses back to the same number at the same precision
+impl fmt::Display for Real {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        // Enough digits to round-trip is somewhere between these two, so search for the fewest
+        let precision = self.precision();
+        let mut lo = cmp::max(((precision - 1) as f64 * LOG10_2).floor() as usize, 1);
+        let mut hi = (precision as f64 * LOG10_2).ceil() as usize + 1;
+
+        let round_trips = |digits: usize| {
+            let (string, exp10) = self.to_digits(digits);
+            let formatted = format_digits(self.negative, &string, exp10);
+            match Real::parse(&formatted, precision) {
+                Ok(parsed) if parsed == *self => Some(formatted),
+                _ => None,
+            }
+        };
+
+        let mut best = match round_trips(lo) {
+            Some(formatted) => return write!(f, "{}", formatted),
+            None => {
+                let (string, exp10) = self.to_digits(hi);
+                format_digits(self.negative, &string, exp10)
+            }
+        };
+
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            match round_trips(mid) {
+                Some(formatted) => {
+                    best = formatted;
+                    hi = mid;
+                }
+                None => lo = mid,
+            }
+        }
+
+        write!(f, "{}", best)
+    }
+}
+
+// A decimal number as written, i.e. an integer and a power of ten
+struct Decimal {
+    negative: bool,
+    int: Vec<u32>,
+    exp10: i64,
+    // Number of significant digits
+    digits: usize,
+}
+
+impl Decimal {
+    fn parse(s: &str) -> Result<Decimal, String> {
+        let s = s.trim();
+
+        // Split off the sign
+        let (negative, s) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+
+        // Split off the exponent
+        let (s, exp10) = match s.find(['e', 'E']) {
+            Some(idx) => match s[idx + 1..].parse::<i64>() {
+                Ok(exp10) if exp10.abs() <= MAX_EXP10 => (&s[..idx], exp10),
+                Ok(_) => return Err(format!("Exponent out of range: {}", &s[idx + 1..])),
+                Err(_) => return Err(format!("Invalid exponent: {}", &s[idx + 1..])),
+            },
+            None => (s, 0),
+        };
+
+        // Read the digits on either side of the decimal point
+        let mut int = vec![0];
+        let mut digits = 0;
+        let mut fraction_digits = 0;
+        let mut seen_point = false;
+        let mut seen_digit = false;
+
+        for c in s.chars() {
+            match c {
+                '0'..='9' => {
+                    let digit = c as u32 - '0' as u32;
+                    int = mul_small(&int, 10, digit);
+                    seen_digit = true;
+
+                    // Leading zeros aren't significant
+                    if digits > 0 || digit != 0 {
+                        digits += 1;
+                    }
+                    if seen_point {
+                        fraction_digits += 1;
+                    }
+                }
+                '.' if !seen_point => seen_point = true,
+                c => return Err(format!("Invalid character in number: {}", c)),
+            }
+        }
+
+        if !seen_digit {
+            return Err("Number has no digits".to_string());
+        }
+
+        Ok(Decimal {
+            negative,
+            int,
+            exp10: exp10 - fraction_digits,
+            digits,
+        })
+    }
+
+    fn to_real(&self, precision: u32) -> Real {
+        // Work with a spare limb, then round at the end
+        let limbs = limbs_for(precision) + 1;
+        let int = Real::from_parts(self.negative, &self.int, 0, limbs);
+
+        if int.is_zero() {
+            return Real::zero(precision);
+        }
+
+        let power = Real::pow10(self.exp10.unsigned_abs(), limbs);
+        let value = if self.exp10 >= 0 {
+            int * power
+        } else {
+            int / power
+        };
+
+        value.with_precision(precision)
+    }
+}
+
+// Lay out significant digits, given the decimal exponent of the first, as a decimal string
+fn format_digits(negative: bool, digits: &str, exp10: i64) -> String {
+    let digits = digits.trim_end_matches('0');
+    let digits = if digits.is_empty() { "0" } else { digits };
+    let sign = if negative { "-" } else { "" };
+
+    if !(-7..21).contains(&exp10) {
+        // Scientific notation
+        let (first, rest) = digits.split_at(1);
+        if rest.is_empty() {
+            format!("{}{}e{}", sign, first, exp10)
+        } else {
+            format!("{}{}.{}e{}", sign, first, rest, exp10)
+        }
+    } else if exp10 < 0 {
+        // All digits after the decimal point
+        let zeros = "0".repeat((-exp10 - 1) as usize);
+        format!("{}0.{}{}", sign, zeros, digits)
+    } else {
+        // Split the digits around the decimal point, padding the integer part if needed
+        let int_len = exp10 as usize + 1;
+        if digits.len() <= int_len {
+            let zeros = "0".repeat(int_len - digits.len());
+            format!("{}{}{}", sign, digits, zeros)
+        } else {
+            let (int, fraction) = digits.split_at(int_len);
+            format!("{}{}.{}", sign, int, fraction)
+        }
+    }
+}
+
+// Number of limbs needed for the given precision in bits
+fn limbs_for(precision: u32) -> usize {
+    cmp::max((precision as u64).div_ceil(LIMB_BITS), 1) as usize
+}
+
+// Multiply an f64 by 2^exponent, for exponents that may be far outside the range of an f64
+pub fn ldexp(value: f64, exponent: i64) -> f64 {
+    let mut value = value;
+    let mut exponent = exponent;
+
+    // Scale in steps that are each representable
+    while exponent > 1000 && value.is_finite() {
+        value *= 2f64.powi(1000);
+        exponent -= 1000;
+    }
+    while exponent < -1000 && value != 0.0 {
+        value *= 2f64.powi(-1000);
+        exponent += 1000;
+    }
+
+    value * 2f64.powi(exponent as i32)
+}
+
+//////////////////////////////////////////////////
+////// Unsigned integer (limb) arithmetic ////////
+//////////////////////////////////////////////////
+
+// Number of significant bits in an unsigned integer
+fn bit_len(int: &[u32]) -> u64 {
+    for (idx, &limb) in int.iter().enumerate().rev() {
+        if limb != 0 {
+            return idx as u64 * LIMB_BITS + (32 - limb.leading_zeros()) as u64;
+        }
+    }
+    0
+}
+
+// Get a single bit of an unsigned integer
+fn bit(int: &[u32], idx: u64) -> bool {
+    match int.get((idx / LIMB_BITS) as usize) {
+        Some(limb) => (limb >> (idx % LIMB_BITS)) & 1 == 1,
+        None => false,
+    }
+}
+
+fn shl(int: &[u32], bits: u64) -> Vec<u32> {
+    let (limb_shift, bit_shift) = ((bits / LIMB_BITS) as usize, bits % LIMB_BITS);
+    let mut result = vec![0; int.len() + limb_shift + 1];
+
+    for (idx, &limb) in int.iter().enumerate() {
+        let wide = (limb as u64) << bit_shift;
+        result[idx + limb_shift] |= wide as u32;
+        result[idx + limb_shift + 1] |= (wide >> 32) as u32;
+    }
+
+    result
+}
+
+fn shr(int: &[u32], bits: u64) -> Vec<u32> {
+    let (limb_shift, bit_shift) = ((bits / LIMB_BITS) as usize, bits % LIMB_BITS);
+    if limb_shift >= int.len() {
+        return vec![0];
+    }
+
+    (0..int.len() - limb_shift)
+        .map(|idx| {
+            let lo = int[idx + limb_shift] as u64;
+            let hi = *int.get(idx + limb_shift + 1).unwrap_or(&0) as u64;
+            (((hi << 32) | lo) >> bit_shift) as u32
+        })
+        .collect()
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    for idx in (0..cmp::max(a.len(), b.len())).rev() {
+        let x = *a.get(idx).unwrap_or(&0);
+        let y = *b.get(idx).unwrap_or(&0);
+        if x != y {
+            return x.cmp(&y);
+        }
+    }
+    Ordering::Equal
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(cmp::max(a.len(), b.len()) + 1);
+    let mut carry = 0u64;
+
+    for idx in 0..cmp::max(a.len(), b.len()) {
+        let sum = *a.get(idx).unwrap_or(&0) as u64 + *b.get(idx).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    result.push(carry as u32);
+
+    result
+}
+
+// Subtract b from a, where a >= b
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+
+    for (idx, &limb) in a.iter().enumerate() {
+        let mut diff = limb as i64 - *b.get(idx).unwrap_or(&0) as i64 - borrow;
+        borrow = if diff < 0 {
+            diff += 1 << 32;
+            1
+        } else {
+            0
+        };
+        result.push(diff as u32);
+    }
+
+    result
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = vec![0u32; a.len() + b.len()];
+
+    for (i, &x) in a.iter().enumerate() {
+        if x == 0 {
+            continue;
+        }
+
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let product = x as u64 * y as u64 + result[i + j] as u64 + carry;
+            result[i + j] = product as u32;
+            carry = product >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+
+    result
+}
+
+// Compute a * m + add, for a single-limb m and add
+fn mul_small(a: &[u32], m: u32, add: u32) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len() + 1);
+    let mut carry = add as u64;
+
+    for &limb in a {
+        let product = limb as u64 * m as u64 + carry;
+        result.push(product as u32);
+        carry = product >> 32;
+    }
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+
+    result
+}
+
+// Compute floor(a / b) by binary long division, where b is non-zero
+fn div_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let len = bit_len(a);
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder = vec![0u32; b.len() + 1];
+
+    for idx in (0..len).rev() {
+        // remainder = remainder * 2 + next bit of a
+        let mut carry = bit(a, idx) as u32;
+        for limb in remainder.iter_mut() {
+            let shifted = (*limb as u64) << 1 | carry as u64;
+            *limb = shifted as u32;
+            carry = (shifted >> 32) as u32;
+        }
+
+        if cmp_mag(&remainder, b) != Ordering::Less {
+            remainder = sub_mag(&remainder, b);
+            quotient[(idx / LIMB_BITS) as usize] |= 1 << (idx % LIMB_BITS);
+        }
+    }
+
+    quotient
+}
+
+// Convert an unsigned integer to a string of decimal digits
+fn to_decimal_string(int: &[u32]) -> String {
+    // Repeatedly divide by 10^9, collecting the remainders
+    let mut int = int.to_vec();
+    let mut chunks = Vec::new();
+
+    while bit_len(&int) > 0 {
+        let mut remainder = 0u64;
+        for limb in int.iter_mut().rev() {
+            let value = (remainder << 32) | *limb as u64;
+            *limb = (value / 1_000_000_000) as u32;
+            remainder = value % 1_000_000_000;
+        }
+        chunks.push(remainder as u32);
+    }
+
+    match chunks.split_last() {
+        None => "0".to_string(),
+        Some((first, rest)) => {
+            let mut string = first.to_string();
+            for chunk in rest.iter().rev() {
+                string.push_str(&format!("{:09}", chunk));
+            }
+            string
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn real(s: &str, precision: u32) -> Real {
+        Real::parse(s, precision).unwrap()
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        let values = [
+            "0.1",
+            "-2.5",
+            "3.14159265358979323846264338327950288419716939937510582097494459",
+            "-1.2345678901234567890123456789e-300",
+            "6.02214076e23",
+            "1e-1000000",
+            "-7e1000000",
+        ];
+
+        for &precision in &[53, 106, 300] {
+            for value in &values {
+                let parsed = real(value, precision);
+                let printed = parsed.to_string();
+                assert_eq!(
+                    real(&printed, precision),
+                    parsed,
+                    "{} at {} bits",
+                    value,
+                    precision
+                );
+                assert_eq!(parsed.precision(), limbs_for(precision) as u32 * 32);
+            }
+        }
+    }
+
+    #[test]
+    fn display_is_shortest() {
+        assert_eq!(real("0.1", 53).to_string(), "0.1");
+        assert_eq!(real("-2.5", 300).to_string(), "-2.5");
+        assert_eq!(real("123456789", 106).to_string(), "123456789");
+        assert_eq!(real("1e-300", 106).to_string(), "1e-300");
+        assert_eq!(real("1e25", 53).to_string(), "1e25");
+        assert_eq!(Real::zero(300).to_string(), "0");
+    }
+
+    #[test]
+    fn parse_keeps_every_digit() {
+        let digits = "0.1000000000000000000000000000000000000000000000000000000000000001";
+        let parsed: Real = digits.parse().unwrap();
+        assert!(parsed > real("0.1", parsed.precision()));
+        assert_eq!(parsed.to_string(), digits);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Real::parse("", 64).is_err());
+        assert!(Real::parse("-", 64).is_err());
+        assert!(Real::parse("1.2.3", 64).is_err());
+        assert!(Real::parse("1e", 64).is_err());
+        assert!(Real::parse("0x10", 64).is_err());
+        assert!(Real::parse("1e999999999999999", 64).is_err());
+        assert!(Real::parse("1e-1000000001", 64).is_err());
+        assert!(Real::parse("1e99999999999999999999", 64).is_err());
+    }
+
+    #[test]
+    fn arithmetic() {
+        for &precision in &[53, 106, 300] {
+            let (a, b) = (real("1.5", precision), real("-2.25", precision));
+            assert_eq!(a.clone() + b.clone(), real("-0.75", precision));
+            assert_eq!(a.clone() - b.clone(), real("3.75", precision));
+            assert_eq!(a.clone() * b.clone(), real("-3.375", precision));
+            assert_eq!(b.clone() / a.clone(), real("-1.5", precision));
+            assert_eq!(
+                real("1", precision) / real("1024", precision),
+                real("0.0009765625", precision)
+            );
+        }
+
+        // Rounding gives the nearest representable value, whatever the precision
+        let third = real("1", 300) / real("3", 300);
+        assert_eq!(third.clone() * real("3", 300), real("1", 300));
+        assert!(third
+            .to_string()
+            .starts_with(&format!("0.{}", "3".repeat(95))));
+        assert_eq!((real("1", 53) / real("3", 53)).to_f64(), 1.0 / 3.0);
+        assert_eq!((real("2", 53) / real("3", 53)).to_f64(), 2.0 / 3.0);
+        assert_eq!((Real::from(0.1) + Real::from(0.2)).to_f64(), 0.1 + 0.2);
+    }
+
+    #[test]
+    fn mixed_precision_takes_the_larger() {
+        let sum = real("1", 53) + real("1e-50", 300);
+        assert_eq!(sum.precision(), 320);
+        assert!(sum > real("1", 320));
+        let error = (sum - real("1", 53) - real("1e-50", 300)).abs();
+        assert!(error.exponent() <= -320);
+    }
+
+    #[test]
+    fn carries() {
+        // Adding into a full limb carries into the next one
+        let sum = real("4294967295", 64) + real("1", 64);
+        assert_eq!(sum, real("4294967296", 64));
+        assert_eq!(sum.exponent(), 33);
+
+        // Rounding up a mantissa of all ones carries into a new bit
+        let rounded = real("4294967295.5", 32);
+        assert_eq!(rounded, real("4294967296", 32));
+        assert_eq!(rounded.exponent(), 33);
+
+        // Subtracting borrows all the way down
+        let difference = real("4294967296", 64) - real("1e-9", 64);
+        assert!(difference < real("4294967296", 64));
+        assert_eq!(difference.exponent(), 32);
+    }
+
+    #[test]
+    fn negative_zero() {
+        let zero = Real::from(-0.0);
+        assert!(zero.is_zero());
+        assert!(!zero.is_sign_negative());
+        assert!(!(-Real::zero(64)).is_sign_negative());
+
+        let difference = real("-1.5", 106) - real("-1.5", 106);
+        assert!(difference.is_zero());
+        assert!(!difference.is_sign_negative());
+        assert_eq!(difference, Real::zero(64));
+        assert_eq!((real("-0", 64) * real("-3", 64)).to_string(), "0");
+        assert_eq!(real("-0", 64).to_f64(), 0.0);
+    }
+
+    #[test]
+    fn exponent_extremes() {
+        let tiny = real("1e-1000000", 106);
+        let huge = real("1e1000000", 106);
+        assert_eq!(tiny.exponent(), -3321928);
+        assert_eq!(huge.exponent(), 3321929);
+        assert_eq!(tiny.to_f64(), 0.0);
+        assert_eq!(huge.to_f64(), f64::INFINITY);
+        assert_eq!((huge.clone() * huge.clone()).to_string(), "1e2000000");
+        assert_eq!((tiny.clone() * huge.clone()).to_string(), "1");
+        assert_eq!((real("1", 106) / tiny.clone()).to_string(), "1e1000000");
+
+        // The mantissa and exponent are still usable outside the range of an f64
+        let (mantissa, exponent) = tiny.to_f64_exp();
+        assert!((0.5..1.0).contains(&mantissa));
+        assert_eq!(exponent, tiny.exponent());
+
+        // Adding numbers too far apart to overlap leaves the larger one
+        assert_eq!(huge.clone() + tiny, huge);
+    }
+
+    #[test]
+    fn f64_conversion() {
+        for &value in &[1.0, -0.1, f64::MAX, f64::MIN_POSITIVE, 5e-324, -1.5e-310] {
+            assert_eq!(Real::from(value).to_f64(), value);
+        }
+        assert_eq!(Real::from(5e-324).exponent(), -1073);
+        assert_eq!(ldexp(1.0, -1074), 5e-324);
+        assert_eq!(ldexp(1.0, -1075), 0.0);
+        assert_eq!(ldexp(0.75, 1024), f64::MAX / (2.0 - 2f64.powi(-52)) * 1.5);
+        assert_eq!(ldexp(1.0, 1024), f64::INFINITY);
+        assert_eq!(ldexp(0.5, -3000), 0.0);
+    }
+}
@@ -2,22 +2,165 @@ extern crate rand;
 
 use rand::{thread_rng, Rng, distributions::Alphanumeric};
 
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::str::FromStr;
 use std::thread::*;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 use std::io::{self, prelude::*};
 
-use crate::cuda::*;
+use crate::buddhabrot::{Buddhabrot, Density};
+use crate::compute::{Backend, ComputeBackend};
+use crate::formula::Formula;
+use crate::newton::Polynomial;
+use crate::perturb::Perturbation;
 use crate::math::*;
 use crate::image::*;
+use crate::lyapunov::Sequence;
+use crate::trap::Trap;
 use crate::colors::*;
 
-#[derive(Clone, Copy, PartialEq)]
+// Fraction of the distance between pixels that orbits have to come back within to count as periodic
+const PERIOD_TOLERANCE: f64 = 1e-3;
+
+// Distance between the pixels iterated by each pass of a progressive render, across and down
+const PASSES: [u32; 4] = [8, 4, 2, 1];
+
+// Width, in characters, of the previews printed to the terminal
+const PREVIEW_COLUMNS: u32 = 80;
+
+// Most bits of precision a window can be rendered with, enough for radii down to about 1e-2400.
+// Arithmetic any more precise than this would be too slow to be useful
+pub const MAX_PRECISION: u32 = 8192;
+
+#[derive(Clone, PartialEq)]
 pub struct Parameters {
     pub image_size: (u32, u32),
     pub supersampling: u32,
-    pub center: Complex,
+    pub center: Complex<Real>,
     pub radius: Real,
     pub max_iter: u32,
+    pub fractal: FractalKind,
+    // Constant c used by the Julia set, whose pixels give the starting z instead
+    pub julia: Complex<Real>,
+    // Power d that z is raised to on each iteration, z^d + c
+    pub power: f64,
+    // Formula to iterate z with instead of the fractal's own, if the user gave one
+    pub formula: Option<Formula>,
+    // Polynomial whose roots the Newton fractal finds
+    pub polynomial: Polynomial,
+    // Settings for the Buddhabrot and Anti-Buddhabrot
+    pub buddhabrot: Buddhabrot,
+    // Sequence of the two rates that the Lyapunov fractal's logistic map takes
+    pub sequence: Sequence,
+    // Whether the CPU backend fills in areas whose edges all come out the same without iterating
+    // them, with Mariani-Silver subdivision.  It's skipped when recording interior stats, orbit
+    // traps or averages, which are different for every pixel.
+    pub subdivision: bool,
+    // Whether to iterate every 8th pixel first, then every 4th, 2nd and finally all of them, with
+    // a preview after each pass
+    pub progressive: bool,
+    // How far z has to get from the origin to count as diverged, and how that's measured
+    pub escape_radius: f64,
+    pub bailout: Bailout,
+    // Whether to record the smallest |z| and the multiplier of the cycle for pixels inside the set,
+    // which means iterating the pixels in the main cardioid and period 2 bulb too
+    pub interior_stats: bool,
+    // Shape to measure each orbit's distance to for orbit trap coloring, if any
+    pub trap: Option<Trap>,
+    // Whether to keep the stripe and triangle inequality averages of each orbit, and how many
+    // stripes the stripe average goes round with for each turn of z about the origin
+    pub averages: bool,
+    pub stripe_density: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+// Which fractal to render.  Other than the Julia set, these all iterate c from the pixel's
+// coordinate, but fold z differently on each iteration.
+pub enum FractalKind {
+    Mandelbrot,
+    Julia,
+    // z <- (|Re z| + i|Im z|)^d + c
+    BurningShip,
+    // z <- conj(z)^d + c, also known as the Mandelbar
+    Tricorn,
+    // z <- |Re z^d| + i Im z^d + c
+    Celtic,
+    // z <- |Re z^d| + i|Im z^d| + c
+    Buffalo,
+    // z <- z - p(z)/p'(z), starting from the pixel's coordinate, until it reaches one of p's roots
+    Newton,
+    // Density of the orbits of z^d + c that escape, which is built up by sampling c rather than
+    // iterating each pixel
+    Buddhabrot,
+    // Density of the orbits that don't escape
+    AntiBuddhabrot,
+    // Lyapunov exponent of the logistic map, x <- r x (1 - x), with r taking the pixel's real and
+    // imaginary parts in turn, as given by the sequence
+    Lyapunov,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+// How a pixel's z is measured against the escape radius to decide whether it has diverged
+pub enum Bailout {
+    // |z| > R
+    Norm,
+    // |Re z| > R
+    Real,
+    // |Im z| > R
+    Imaginary,
+    // |Re z| + |Im z| > R
+    Manhattan,
+}
+
+impl Bailout {
+    // Whether z has escaped past the given radius
+    pub fn escaped<T: Float>(self, z: &Complex<T>, radius: f64) -> bool {
+        match self {
+            Bailout::Norm => z.abs_squared().to_f64() > radius * radius,
+            Bailout::Real => z.0.to_f64().abs() > radius,
+            Bailout::Imaginary => z.1.to_f64().abs() > radius,
+            Bailout::Manhattan => z.0.to_f64().abs() + z.1.to_f64().abs() > radius,
+        }
+    }
+}
+
+impl FractalKind {
+    // Raise z to the render's power with `pow`, folding it before or after as this fractal does
+    pub fn pow<T: Float, F>(self, z: &Complex<T>, pow: F) -> Complex<T>
+    where
+        F: Fn(&Complex<T>) -> Complex<T>,
+    {
+        match self {
+            // The Newton fractal doesn't raise z to a power at all, so it's left alone
+            FractalKind::Mandelbrot
+            | FractalKind::Julia
+            | FractalKind::Newton
+            | FractalKind::Buddhabrot
+            | FractalKind::AntiBuddhabrot
+            | FractalKind::Lyapunov => pow(z),
+            FractalKind::BurningShip => pow(&z.abs_components()),
+            FractalKind::Tricorn => pow(&z.conj()),
+            FractalKind::Celtic => {
+                let z = pow(z);
+                Complex(abs(z.0), z.1)
+            }
+            FractalKind::Buffalo => pow(z).abs_components(),
+        }
+    }
+
+    // Whether the fractal is usually shown with the imaginary axis pointing down
+    pub fn flipped(self) -> bool {
+        self == FractalKind::BurningShip
+    }
+
+    // Whether the fractal is rendered as the density of the orbits that pass through each pixel,
+    // rather than by iterating each pixel
+    pub fn density(self) -> bool {
+        matches!(self, FractalKind::Buddhabrot | FractalKind::AntiBuddhabrot)
+    }
 }
 
 #[derive(Clone)]
@@ -25,24 +168,324 @@ pub struct Render {
     pub params: Parameters,
     pub iterations: u32,
     pub pixels: Vec<(u32, Complex, Complex, bool)>,
+    // Anything else worked out for each pixel, in the same order as the pixels
+    pub details: Vec<PixelDetails>,
+    // Extra state kept by the perturbation backend, so that it can pick up where it left off
+    pub perturbation: Option<Perturbation>,
+    // Hits counted so far by the Buddhabrot, which it adds to when it picks up where it left off
+    pub density: Option<Density>,
+    // Only the pixels on every stride-th column and row are iterated, and the rest are left as they
+    // are, so that progressive rendering can start with a coarse pass
+    pub stride: u32,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+// Results for a pixel beyond its iterations, z and whether it's finished, which only some fractals
+// and color functions need
+pub struct PixelDetails {
+    // Which of the Newton fractal's roots the pixel converged to
+    pub root: Option<u32>,
+    // Lyapunov exponent of the pixel's logistic map, which is negative where it's stable and
+    // positive where it's chaotic
+    pub exponent: f64,
+    // Whether the pixel was proven to be inside the set, either straight away or by its orbit
+    // repeating, rather than reaching the max iterations
+    pub interior: bool,
+    // Period of the cycle that the pixel's orbit settles into, if it was found
+    pub period: Option<u32>,
+    // Whether the pixel was filled in from its neighbours by subdivision, rather than iterated
+    pub guessed: bool,
+    // Derivative of z with respect to c (or for the Julia set, the starting z) so far, which gives
+    // the distance estimate once the pixel diverges.  It's picked up from like z, and only tracked
+    // for the escape-time fractals without a formula.
+    pub derivative: Complex,
+    // Smallest |z| the orbit has reached, and the iteration it reached it at, if the render records
+    // interior stats and the pixel hasn't diverged
+    pub minimum: Option<(f64, u32)>,
+    // Size of the multiplier of the cycle the orbit settles into, |dz/dz| over one period, which
+    // goes from 0 at the middle of a component of the set to 1 at its edge
+    pub multiplier: Option<f64>,
+    // Closest the orbit has come to the orbit trap, and the z it came closest at, if there is one
+    pub trap: Option<(f64, Complex)>,
+    // Averages of sin(arg z) and of where |z| falls between its triangle inequality bounds, over the
+    // orbit, if the render keeps them
+    pub stripe: Option<Average>,
+    pub triangle: Option<Average>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+// Running average of some statistic of an orbit, which remembers its last term so that it can be
+// blended with the average before it
+pub struct Average {
+    pub sum: f64,
+    pub last: f64,
+    pub count: u32,
+}
+
+impl Average {
+    pub fn add(&mut self, term: f64) {
+        self.sum += term;
+        self.last = term;
+        self.count += 1;
+    }
+
+    // The average, blended towards the average without the last term by the given fraction, so that
+    // with the smooth iteration count's fraction it changes smoothly between escape iterations
+    pub fn smooth(&self, fraction: f64) -> f64 {
+        let average = self.sum / self.count as f64;
+        if self.count < 2 {
+            return average;
+        }
+
+        let previous = (self.sum - self.last) / (self.count - 1) as f64;
+        let fraction = fraction.clamp(0.0, 1.0);
+        average + (previous - average) * fraction
+    }
+}
+
+impl PixelDetails {
+    // Take |z| after the given iteration into account for the smallest |z|.  Only pixels that don't
+    // diverge keep it, so that the outside of the set comes out the same with or without it.
+    pub fn record_minimum(&mut self, size: f64, i: u32, diverged: bool) {
+        if diverged {
+            self.minimum = None;
+        } else if self.minimum.is_none_or(|(minimum, _)| size < minimum) {
+            self.minimum = Some((size, i));
+        }
+    }
+
+    // Take z into account for the closest the orbit has come to the trap
+    pub fn record_trap(&mut self, trap: &Trap, z: Complex) {
+        let distance = trap.distance(z);
+        if self.trap.is_none_or(|(closest, _)| distance < closest) {
+            self.trap = Some((distance, z));
+        }
+    }
+
+    // Take the step from `previous` to z, with the constant c and the power, into account for the
+    // averages.  The first iteration is left out, since it doesn't say anything about the orbit yet
+    // (and the triangle inequality bounds meet there for the Mandelbrot set).  The triangle
+    // inequality average needs the power, so there isn't one for formulas.
+    pub fn record_averages(
+        &mut self,
+        (previous, z, c): (Complex, Complex, Complex),
+        i: u32,
+        power: Option<f64>,
+        density: f64,
+    ) {
+        if i < 2 {
+            return;
+        }
+
+        let angle = z.1.atan2(z.0);
+        self.stripe
+            .get_or_insert_with(Average::default)
+            .add(0.5 + 0.5 * (density * angle).sin());
+
+        // |z| is somewhere between ||z'^p| - |c|| and |z'^p| + |c|
+        if let Some(power) = power {
+            let (size, constant) = (previous.abs().powf(power), c.abs());
+            let low = (size - constant).abs();
+            let high = size + constant;
+            if high > low {
+                self.triangle
+                    .get_or_insert_with(Average::default)
+                    .add((z.abs() - low) / (high - low));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderError(pub String);
+
+// Preview made after one of a progressive render's passes: the pass it was made after, the image,
+// and where it was exported to if the job exports its images
+pub type Preview = (u32, Image, Option<String>);
+
 pub struct RenderJob {
     thread: JoinHandle<std::result::Result<(Render, Option<String>), String>>,
     progress: Arc<Mutex<Option<f64>>>,
+    // Which pass the job is on, counting from 1, out of how many
+    pass: Arc<AtomicU32>,
+    passes: u32,
+    preview: Arc<Mutex<Option<Preview>>>,
+    cancel: Arc<AtomicBool>,
 }
 
-impl Render {
-    pub fn default() -> Render {
-        Render::new(Parameters {
+impl Parameters {
+    // Number of bits of precision needed to tell neighbouring pixels apart (plus a few spare, since
+    // the iteration amplifies rounding errors), given the number of pixels in image_size.  It's
+    // capped at MAX_PRECISION, beyond which neighbouring pixels may come out the same
+    pub fn precision(&self) -> u32 {
+        let bits = std::cmp::max(self.bits_needed(), 53);
+        u32::try_from(bits).map_or(MAX_PRECISION, |bits| std::cmp::min(bits, MAX_PRECISION))
+    }
+
+    // Check that the window can be told apart at MAX_PRECISION, for rejecting deeper zooms (or
+    // further-out centers) when they're set rather than computing with them
+    pub fn check_precision(&self) -> std::result::Result<(), String> {
+        if self.bits_needed() > i64::from(MAX_PRECISION) {
+            return Err(format!(
+                "Window needs more than the maximum of {} bits of precision",
+                MAX_PRECISION
+            ));
+        }
+        Ok(())
+    }
+
+    fn bits_needed(&self) -> i64 {
+        let pixels = std::cmp::max(self.image_size.0, self.image_size.1) as f64;
+
+        // Bits needed for the integer part of the center, and for the distance between pixels
+        let magnitude = std::cmp::max(
+            std::cmp::max(self.center.0.exponent(), self.center.1.exponent()),
+            1,
+        );
+        let spacing = self.radius.exponent() - pixels.log2().ceil() as i64;
+
+        magnitude - spacing + 12
+    }
+
+    // Distance within which an orbit counts as having come back to an earlier point, when checking
+    // for periodic orbits.  It's a small fraction of the distance between pixels, so that orbits
+    // which escape very slowly aren't mistaken for periodic ones.
+    pub fn period_tolerance(&self) -> f64 {
+        self.pixel_size() * PERIOD_TOLERANCE
+    }
+
+    // Distance between neighbouring pixels on the complex plane
+    pub fn pixel_size(&self) -> f64 {
+        let pixels = std::cmp::min(self.image_size.0, self.image_size.1) as f64;
+        self.radius.to_f64() * 2.0 / pixels
+    }
+
+    // The constant c that every pixel shares, if the Julia set is being rendered
+    pub fn julia_constant(&self) -> Option<&Complex<Real>> {
+        match self.fractal {
+            FractalKind::Julia => Some(&self.julia),
+            _ => None,
+        }
+    }
+
+    // Whether pixels in the main cardioid or period 2 bulb can be marked as inside the Mandelbrot
+    // set up front.  They're known to be inside, as long as f64s are precise enough to tell, and so
+    // are their periods.  Their orbits stay within |z| <= 2, so they only never escape with the
+    // usual bailout and a large enough radius.  Recording interior stats or orbit traps needs their
+    // orbits, though.
+    fn interior_test(&self) -> bool {
+        self.fractal == FractalKind::Mandelbrot
+            && self.power == 2.0
+            && self.formula.is_none()
+            && self.escape_radius >= 2.0
+            && self.bailout == Bailout::Norm
+            && self.precision() <= 53
+            && !self.interior_stats
+            && self.trap.is_none()
+    }
+
+    // The state a pixel starts from, given its point on the complex plane.  The Julia set iterates
+    // the same c everywhere, starting from the pixel's point, and the Newton fractal starts from
+    // the pixel's point without using c at all.  The Lyapunov fractal keeps its rates in c, and the
+    // logistic map's x in z, which starts from a half.
+    fn start_pixel(
+        &self,
+        point: Complex,
+        interior_test: bool,
+    ) -> ((u32, Complex, Complex, bool), PixelDetails) {
+        let mut details = PixelDetails::default();
+
+        let pixel = match self.fractal {
+            FractalKind::Julia => {
+                details.derivative = Complex(1.0, 0.0);
+                (0, self.julia.to_f64(), point, false)
+            }
+            FractalKind::Newton => (0, point, point, false),
+            FractalKind::Lyapunov => (0, point, Complex(0.5, 0.0), false),
+            _ if interior_test && main_bulb_period(point).is_some() => {
+                details.interior = true;
+                details.period = main_bulb_period(point);
+                (self.max_iter, point, Complex(0.0, 0.0), false)
+            }
+            _ => (0, point, Complex(0.0, 0.0), false),
+        };
+
+        (pixel, details)
+    }
+}
+
+// If c is in the Mandelbrot set's main cardioid or its period 2 bulb, where every point is known
+// not to escape, the period of the cycle that its orbit settles into
+pub fn main_bulb_period(c: Complex) -> Option<u32> {
+    let Complex(x, y) = c;
+
+    // The main cardioid, then the period 2 bulb, which is the disc of radius 1/4 around -1
+    let q = (x - 0.25) * (x - 0.25) + y * y;
+    if q * (q + (x - 0.25)) <= 0.25 * y * y {
+        Some(1)
+    } else if (x + 1.0) * (x + 1.0) + y * y <= 0.0625 {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+// Next value of the derivative of z, given z before the iteration: d' = p z^(p-1) d + 1, or without
+// the 1 for the Julia set, where it's with respect to the starting z rather than c.  The folded
+// fractals aren't analytic, so for them it's only an estimate that leaves the fold out.
+pub fn next_derivative(z: Complex, derivative: Complex, power: f64, julia: bool) -> Complex {
+    let step = slope(z, power) * derivative;
+
+    if julia {
+        step
+    } else {
+        step + Complex(1.0, 0.0)
+    }
+}
+
+// Derivative of z^p at z, p z^(p-1)
+pub fn slope(z: Complex, power: f64) -> Complex {
+    if power == 2.0 {
+        z + z
+    } else {
+        z.pow(power - 1.0) * Complex(power, 0.0)
+    }
+}
+
+impl Default for Parameters {
+    fn default() -> Parameters {
+        Parameters {
             image_size: (1000, 1000),
             supersampling: 1,
-            center: Complex(0.0, 0.0),
-            radius: 2.0,
+            center: Complex(Real::from(0.0), Real::from(0.0)),
+            radius: Real::from(2.0),
             max_iter: 500,
-        })
+            fractal: FractalKind::Mandelbrot,
+            julia: Complex("-0.123".parse().unwrap(), "0.745".parse().unwrap()),
+            power: 2.0,
+            formula: None,
+            polynomial: Polynomial::default(),
+            buddhabrot: Buddhabrot::default(),
+            sequence: Sequence::default(),
+            subdivision: true,
+            progressive: false,
+            escape_radius: 2.0,
+            bailout: Bailout::Norm,
+            interior_stats: false,
+            trap: None,
+            averages: false,
+            stripe_density: 5.0,
+        }
     }
+}
 
+impl Default for Render {
+    fn default() -> Render {
+        Render::new(Parameters::default())
+    }
+}
+
+impl Render {
     pub fn new(mut params: Parameters) -> Render {
         // Verify supersampling
         if params.supersampling == 0 {
@@ -53,34 +496,45 @@ impl Render {
         params.image_size.0 *= params.supersampling;
         params.image_size.1 *= params.supersampling;
 
-        // Create the list of pixels
-        let mut pixels = Vec::with_capacity((params.image_size.0 * params.image_size.1) as usize);
-
         // Prepare the mapping (for faster calculations later)
-        let mapping = Complex::get_mapping(params.image_size, (params.center, params.radius));
+        let mapping = Complex::get_mapping(
+            params.image_size,
+            (&params.center, &params.radius),
+            params.fractal.flipped(),
+        );
+        let interior_test = params.interior_test();
 
         // Populate the list
-        for idx in 0..pixels.capacity() {
-            let x = idx as u32 % params.image_size.0;
-            let y = idx as u32 / params.image_size.0;
+        let (pixels, details) = (0..params.image_size.0 * params.image_size.1)
+            .map(|idx| {
+                let x = idx % params.image_size.0;
+                let y = idx / params.image_size.0;
 
-            // Convert the (x, y) image coords to complex coords based on the window
-            let complex = mapping(x, y);
-
-            // Insert Pixel into vector
-            pixels.push((0, complex, Complex(0.0, 0.0), false));
-        }
+                // Convert the (x, y) image coords to complex coords based on the window
+                params.start_pixel(mapping(x, y), interior_test)
+            })
+            .unzip();
 
         Render {
             params,
             iterations: 0,
+            details,
             pixels,
+            perturbation: None,
+            density: None,
+            stride: 1,
         }
     }
 
     // Using the params, recalculate the pixel array
     pub fn recalc(&mut self, params: &Parameters) {
-        if self.params == *params {
+        // The Buddhabrot can take more samples on top of the hits it already has, and rendering
+        // progressively doesn't change the pixels
+        let mut unchanged = self.params.clone();
+        unchanged.buddhabrot.samples = params.buddhabrot.samples;
+        unchanged.progressive = params.progressive;
+
+        if unchanged == *params {
             // We won't need to recalculate the pixel array
             self.params = params.clone();
         } else {
@@ -89,45 +543,163 @@ impl Render {
         }
     }
 
+    // Guessed pixels that didn't diverge only hold for the iterations they were filled in with, so
+    // with more iterations they start again from the beginning, whichever backend picks them up
+    fn restart_guessed(&mut self) {
+        let params = &self.params;
+        let mapping = Complex::get_mapping(
+            params.image_size,
+            (&params.center, &params.radius),
+            params.fractal.flipped(),
+        );
+        let interior_test = params.interior_test();
+
+        let pixels = self.pixels.iter_mut().zip(&mut self.details);
+        for (idx, (pixel, details)) in pixels.enumerate() {
+            if details.guessed && !pixel.3 && pixel.0 < params.max_iter {
+                let x = idx as u32 % params.image_size.0;
+                let y = idx as u32 / params.image_size.0;
+                let (start, start_details) = params.start_pixel(mapping(x, y), interior_test);
+                *pixel = start;
+                *details = start_details;
+
+                // The perturbation backend would otherwise pick up from the pixel's old offset
+                if let Some(state) = &mut self.perturbation {
+                    state.deltas[idx] = None;
+                }
+            }
+        }
+    }
+
+    // Fraction of the pixels that subdivision filled in rather than iterating
+    pub fn guessed(&self) -> f64 {
+        let guessed = self.details.iter().filter(|details| details.guessed).count();
+        guessed as f64 / self.details.len() as f64
+    }
+
     // Run a specified number of iterations on the Render
-    pub fn run(self) -> RenderJob {
+    pub fn run(self, backend: Backend) -> RenderJob {
+        // Create a RenderJob and return it
+        RenderJob::new(self, "".into(), false, backend)
+    }
+
+    // Run the Render, coloring a preview with the given color function after each pass if it's
+    // rendered progressively
+    pub fn run_with_previews(self, colorfunc: ColorFunction, backend: Backend) -> RenderJob {
         // Create a RenderJob and return it
-        RenderJob::new(self, "".into())
+        RenderJob::new(self, colorfunc.info(), false, backend)
     }
 
-    pub fn run_and_export(self, colorfunc: ColorFunction) -> RenderJob {
+    pub fn run_and_export(self, colorfunc: ColorFunction, backend: Backend) -> RenderJob {
         // Create a RenderJob and return it
-        RenderJob::new(self, colorfunc.info())
+        RenderJob::new(self, colorfunc.info(), true, backend)
+    }
+
+    // Number of passes the render takes.  The Buddhabrot's samples don't belong to any one pixel,
+    // so it can't be rendered progressively.
+    fn passes(&self) -> u32 {
+        if self.params.progressive && !self.params.fractal.density() {
+            PASSES.len() as u32
+        } else {
+            1
+        }
     }
 }
 
+// Make a path for an image in the system's temp directory
+fn temp_path() -> String {
+    let rand_string: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .collect();
+    format!("{}/mandelbrot{}.png", std::env::temp_dir().as_path().display(), rand_string)
+}
+
+// Compute a render one pass at a time, iterating every 8th pixel across and down, then every 4th,
+// every 2nd, and finally all of them.  Pixels finished by one pass are already done for the next,
+// so every pixel is still only iterated once.  After every pass but the last, `preview` is given
+// the pixels iterated so far.
+fn compute_passes<P: FnMut(u32, &Render)>(
+    mut render: Render,
+    backend: &dyn ComputeBackend,
+    progress: &Arc<Mutex<Option<f64>>>,
+    (pass, cancel): (&AtomicU32, &Arc<AtomicBool>),
+    mut preview: P,
+) -> std::result::Result<Render, RenderError> {
+    for (n, step) in PASSES.iter().enumerate() {
+        pass.store(n as u32 + 1, Ordering::Relaxed);
+        *progress.lock().unwrap() = Some(0.0);
+
+        render.stride = *step;
+        render = backend.compute(render, Arc::clone(progress), Arc::clone(cancel))?;
+
+        if *step > 1 {
+            preview(*step, &render);
+        }
+    }
+
+    Ok(render)
+}
+
 impl RenderJob {
-    fn new(mut render: Render, colorfunc: String) -> RenderJob {
+    fn new(mut render: Render, colorfunc: String, export: bool, backend: Backend) -> RenderJob {
         let progress = Arc::new(Mutex::new(Some(0.0)));
+        let pass = Arc::new(AtomicU32::new(1));
+        let passes = render.passes();
+        let preview = Arc::new(Mutex::new(None));
+        let cancel = Arc::new(AtomicBool::new(false));
+        render.restart_guessed();
 
         let thread = {
             let progress = Arc::clone(&progress);
+            let pass = Arc::clone(&pass);
+            let preview = Arc::clone(&preview);
+            let cancel = Arc::clone(&cancel);
 
             std::thread::spawn(move || {
-                // Call the CUDA code, passing the render struct
-                let result = compute(render.clone(), Arc::clone(&progress));
+                // Color a preview of the pixels so far, and export it if the final image will be
+                let make_preview = |step: u32, render: &Render| {
+                    if colorfunc.is_empty() {
+                        return;
+                    }
 
-                match result {
+                    let colors = colorfunc.parse::<ColorFunction>().unwrap();
+                    let image = Image::preview(render, colors, step);
+                    let path = if export {
+                        let path = temp_path();
+                        image.export(path.clone()).ok().map(|_| path)
+                    } else {
+                        None
+                    };
+
+                    *preview.lock().unwrap() = Some((pass.load(Ordering::Relaxed), image, path));
+                };
+
+                // Pass the render struct to the chosen backend
+                let result = backend.get(&render.params).and_then(|backend| {
+                    if passes > 1 {
+                        compute_passes(
+                            render.clone(),
+                            &*backend,
+                            &progress,
+                            (&pass, &cancel),
+                            make_preview,
+                        )
+                    } else {
+                        backend.compute(render.clone(), Arc::clone(&progress), Arc::clone(&cancel))
+                    }
+                });
 
+                match result {
                     Ok(result) => {
-                        // Update the data in the Render with the new data
-                        render.pixels = result.pixels;
-                        render.iterations = result.iterations;
+                        // Update the data in the Render with the new data, including any state
+                        // the backend keeps to pick up from later
+                        render = result;
 
-                        if colorfunc != "" {
+                        if export {
                             // Export the image
                             let image = Image::new(&render, colorfunc.parse::<ColorFunction>().unwrap());
-
-                            let rand_string: String = thread_rng()
-                                .sample_iter(&Alphanumeric)
-                                .take(32)
-                                .collect();
-                            let path = format!("{}/mandelbrot{}.png", std::env::temp_dir().as_path().display(), rand_string);
+                            let path = temp_path();
 
                             // Export the image
                             match image.export(path.clone()) {
@@ -150,6 +722,7 @@ impl RenderJob {
                     }
                     Err(RenderError(message)) => {
                         // There was an error, return the message
+                        *progress.lock().unwrap() = None;
                         Err(message)
                     }
                 }
@@ -157,16 +730,39 @@ impl RenderJob {
         };
 
         // Return the created job
-        RenderJob { thread, progress }
+        RenderJob {
+            thread,
+            progress,
+            pass,
+            passes,
+            preview,
+            cancel,
+        }
+    }
+
+    /// Ask the backend to stop the job.  The job will finish with an error once the backend
+    /// notices, which may not be immediate.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
     }
 
     /// Wait for the thread to finish.  This method blocks, and returns the render or error message
     /// when the thread is finished.  It also prints out the progress until it returns.
     pub fn join_with_progress(self) -> std::result::Result<(Render, Option<String>), String> {
         // Progress loop until 100 is returned
-        while let Some(progress) = self.progress() {
+        while let Some((pass, progress)) = self.progress() {
+            // Show each pass's preview as soon as it's ready
+            if let Some((pass, image, _)) = self.preview() {
+                println!("\rPass {} of {}:               ", pass, self.passes);
+                image.print(PREVIEW_COLUMNS);
+            }
+
             // Print the progress
-            print!("\rProgress: {:.*}% ", 2, progress);
+            if self.passes > 1 {
+                print!("\rPass {} of {}, progress: {:.*}% ", pass, self.passes, 2, progress);
+            } else {
+                print!("\rProgress: {:.*}% ", 2, progress);
+            }
             io::stdout().flush().unwrap();
         }
 
@@ -184,8 +780,303 @@ impl RenderJob {
     }
 
     /// Get the progress of the job at the current time.  This method may block very briefly if the
-    /// progress mutex is locked.  Prints None if the job is complete
-    pub fn progress(&self) -> Option<f64> {
-        *self.progress.lock().unwrap()
+    /// progress mutex is locked.  Returns the pass the job is on (counting from 1) along with the
+    /// percentage of that pass completed, or None if the job is complete.
+    pub fn progress(&self) -> Option<(u32, f64)> {
+        let progress = *self.progress.lock().unwrap();
+        progress.map(|progress| (self.pass.load(Ordering::Relaxed), progress))
+    }
+
+    /// Get the number of passes the job takes, which is 1 unless it's rendered progressively.
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
+    /// Take the preview made after the latest pass, if there has been a new one since this was
+    /// last called.
+    pub fn preview(&self) -> Option<Preview> {
+        self.preview.lock().unwrap().take()
+    }
+}
+
+// Allow for parsing fractals from user input
+impl FromStr for FractalKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim() {
+            "mandelbrot" => Ok(FractalKind::Mandelbrot),
+            "julia" => Ok(FractalKind::Julia),
+            "burningship" => Ok(FractalKind::BurningShip),
+            "tricorn" => Ok(FractalKind::Tricorn),
+            "celtic" => Ok(FractalKind::Celtic),
+            "buffalo" => Ok(FractalKind::Buffalo),
+            "newton" => Ok(FractalKind::Newton),
+            "buddhabrot" => Ok(FractalKind::Buddhabrot),
+            "antibuddhabrot" => Ok(FractalKind::AntiBuddhabrot),
+            "lyapunov" => Ok(FractalKind::Lyapunov),
+            s => Err(format!("No such fractal: {}.", s)),
+        }
+    }
+}
+
+impl fmt::Display for FractalKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FractalKind::Mandelbrot => write!(f, "mandelbrot"),
+            FractalKind::Julia => write!(f, "julia"),
+            FractalKind::BurningShip => write!(f, "burningship"),
+            FractalKind::Tricorn => write!(f, "tricorn"),
+            FractalKind::Celtic => write!(f, "celtic"),
+            FractalKind::Buffalo => write!(f, "buffalo"),
+            FractalKind::Newton => write!(f, "newton"),
+            FractalKind::Buddhabrot => write!(f, "buddhabrot"),
+            FractalKind::AntiBuddhabrot => write!(f, "antibuddhabrot"),
+            FractalKind::Lyapunov => write!(f, "lyapunov"),
+        }
+    }
+}
+
+// Allow for parsing bailouts from user input
+impl FromStr for Bailout {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim() {
+            "norm" => Ok(Bailout::Norm),
+            "real" => Ok(Bailout::Real),
+            "imag" => Ok(Bailout::Imaginary),
+            "manhattan" => Ok(Bailout::Manhattan),
+            s => Err(format!("No such bailout: {}.", s)),
+        }
+    }
+}
+
+impl fmt::Display for Bailout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Bailout::Norm => write!(f, "norm"),
+            Bailout::Real => write!(f, "real"),
+            Bailout::Imaginary => write!(f, "imag"),
+            Bailout::Manhattan => write!(f, "manhattan"),
+        }
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RenderError: {}", self.0)
+    }
+}
+
+impl error::Error for RenderError {
+    fn description(&self) -> &str {
+        &self.0[..]
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu;
+    use std::sync::mpsc;
+
+    fn compute(render: Render) -> Render {
+        let progress = Arc::new(Mutex::new(None));
+        let cancel = Arc::new(AtomicBool::new(false));
+        cpu::compute(render, progress, cancel).unwrap()
+    }
+
+    #[test]
+    fn precision_is_capped() {
+        let window = |center: &str, radius: &str| Parameters {
+            image_size: (8, 8),
+            center: Complex(center.parse().unwrap(), Real::from(0.0)),
+            radius: radius.parse().unwrap(),
+            ..Parameters::default()
+        };
+
+        assert_eq!(window("-0.5", "2").precision(), 53);
+        assert!(window("-0.5", "1e-2000").check_precision().is_ok());
+
+        // Windows too deep or too far out are rejected, and would be rendered at MAX_PRECISION
+        for params in &[window("-0.5", "1e-99999999"), window("1e99999999", "1")] {
+            assert!(params.check_precision().is_err());
+            assert_eq!(params.precision(), MAX_PRECISION);
+        }
+    }
+
+    #[test]
+    fn main_bulbs_are_only_skipped_when_their_orbits_never_escape() {
+        let params = Parameters {
+            image_size: (64, 48),
+            ..Parameters::default()
+        };
+        let skipped = |params: Parameters| {
+            let render = Render::new(params);
+            render.details.iter().filter(|details| details.interior).count()
+        };
+        assert!(params.interior_test());
+        assert!(skipped(params.clone()) > 0);
+
+        // Points in the main bulbs can escape past a smaller radius, or other bailouts' radii
+        let escaping = [
+            Parameters {
+                escape_radius: 1.5,
+                ..params.clone()
+            },
+            Parameters {
+                bailout: Bailout::Manhattan,
+                ..params.clone()
+            },
+            Parameters {
+                bailout: Bailout::Real,
+                ..params.clone()
+            },
+        ];
+        for params in &escaping {
+            assert!(!params.interior_test());
+            assert_eq!(skipped(params.clone()), 0);
+        }
+
+        // Nor are they skipped when their orbits are needed
+        assert!(!Parameters {
+            interior_stats: true,
+            ..params.clone()
+        }
+        .interior_test());
+        assert!(!Parameters {
+            power: 3.0,
+            ..params
+        }
+        .interior_test());
+    }
+
+    #[test]
+    fn guessed_pixels_restart_from_their_starting_point() {
+        let params = Parameters {
+            image_size: (96, 96),
+            fractal: FractalKind::Julia,
+            max_iter: 100,
+            ..Parameters::default()
+        };
+        let mut render = compute(Render::new(params.clone()));
+        assert!(render.guessed() > 0.0);
+
+        // With more iterations, the guessed pixels are as they were before they were iterated
+        let start = Render::new(Parameters {
+            max_iter: 200,
+            ..params
+        });
+        let before = render.clone();
+        render.params.max_iter = 200;
+        render.restart_guessed();
+
+        assert_eq!(render.guessed(), 0.0);
+        for idx in 0..render.pixels.len() {
+            if before.details[idx].guessed {
+                assert_eq!(render.pixels[idx], start.pixels[idx]);
+                assert_eq!(render.details[idx], start.details[idx]);
+                assert_eq!(render.details[idx].derivative, Complex(1.0, 0.0));
+            } else {
+                assert_eq!(render.pixels[idx], before.pixels[idx]);
+                assert_eq!(render.details[idx], before.details[idx]);
+            }
+        }
+    }
+
+    // Backend that iterates on the CPU, but reports the stride it's asked for and then waits for
+    // the go-ahead before each pass
+    struct Stepped {
+        strides: mpsc::Sender<u32>,
+        go: Mutex<mpsc::Receiver<()>>,
+    }
+
+    impl ComputeBackend for Stepped {
+        fn name(&self) -> &'static str {
+            "stepped"
+        }
+
+        fn available(&self) -> bool {
+            true
+        }
+
+        fn compute(
+            &self,
+            render: Render,
+            progress: Arc<Mutex<Option<f64>>>,
+            cancel: Arc<AtomicBool>,
+        ) -> crate::compute::Result {
+            self.strides.send(render.stride).unwrap();
+            self.go.lock().unwrap().recv().unwrap();
+            cpu::compute(render, progress, cancel)
+        }
+    }
+
+    fn progressive(progressive: bool) -> Parameters {
+        Parameters {
+            image_size: (72, 56),
+            center: Complex("-0.5".parse().unwrap(), "0".parse().unwrap()),
+            radius: "1.25".parse().unwrap(),
+            max_iter: 200,
+            progressive,
+            ..Parameters::default()
+        }
+    }
+
+    #[test]
+    fn progressive_render_matches_a_single_pass() {
+        let (single, _) = Render::new(progressive(false)).run(Backend::Cpu).join().unwrap();
+        let render = Render::new(progressive(true));
+        assert_eq!(render.passes(), 4);
+        let (passes, _) = render.run(Backend::Cpu).join().unwrap();
+
+        assert_eq!(passes.pixels, single.pixels);
+        assert_eq!(passes.details, single.details);
+        assert_eq!(passes.iterations, single.iterations);
+    }
+
+    #[test]
+    fn progress_reports_each_pass() {
+        let (strides_sender, strides) = mpsc::channel();
+        let (go, go_receiver) = mpsc::channel();
+        let backend = Stepped {
+            strides: strides_sender,
+            go: Mutex::new(go_receiver),
+        };
+
+        let render = Render::new(progressive(true));
+        let progress = Arc::new(Mutex::new(Some(0.0)));
+        let pass = Arc::new(AtomicU32::new(1));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (progress, pass, cancel) = (progress.clone(), pass.clone(), cancel.clone());
+            spawn(move || {
+                compute_passes(render, &backend, &progress, (&pass, &cancel), |_, _| ())
+                    .map(|render| (render, None))
+                    .map_err(|RenderError(message)| message)
+            })
+        };
+        let job = RenderJob {
+            thread,
+            progress,
+            pass,
+            passes: 4,
+            preview: Arc::new(Mutex::new(None)),
+            cancel,
+        };
+
+        // Each pass starts over from 0% with every pixel further apart than the last
+        for (n, stride) in PASSES.iter().enumerate() {
+            assert_eq!(strides.recv().unwrap(), *stride);
+            assert_eq!(job.progress(), Some((n as u32 + 1, 0.0)));
+            go.send(()).unwrap();
+        }
+        assert_eq!(job.passes(), 4);
+        assert!(job.join().is_ok());
     }
 }
@@ -0,0 +1,177 @@
+use crate::math::{Complex, Float};
+use crate::render::Bailout;
+
+// Number of terms in the series
+const TERMS: usize = 6;
+
+// Largest error allowed at a probe point, relative to the size of its offset from the reference
+const TOLERANCE: f64 = 1e-12;
+
+#[derive(Clone)]
+// Approximation of a pixel's offset from the reference orbit after some number of iterations, as a
+// polynomial in its c's offset from the reference's c:
+//   dz = A dc + B dc^2 + C dc^3 + ...
+// or for the Julia set, in its starting z's offset from the reference's.  The coefficients only
+// depend on the reference orbit, so every pixel can skip those iterations.
+pub struct Series<T> {
+    // Number of iterations the series skips
+    pub iterations: u32,
+    coefficients: Vec<Complex<T>>,
+}
+
+impl<T: Float> Series<T> {
+    // Find out how many iterations of the reference orbit can be skipped.  The probes (offsets from
+    // the reference, which should cover the window) are iterated exactly alongside the series, and
+    // it's only used for as long as it agrees with all of them.
+    pub fn new(
+        orbit: &[Complex],
+        probes: &[Complex<T>],
+        max_iter: u32,
+        julia: bool,
+        (bailout, radius): (Bailout, f64),
+    ) -> Series<T> {
+        let zero = Complex(T::from_f64(0.0), T::from_f64(0.0));
+        let one = Complex(T::from_f64(1.0), T::from_f64(0.0));
+        let tolerance = T::from_f64(TOLERANCE * TOLERANCE);
+
+        // The Julia set starts off with dz being the offset itself, while c's offset only comes in
+        // with the first iteration
+        let mut series = Series {
+            iterations: 0,
+            coefficients: vec![zero.clone(); TERMS],
+        };
+        let mut deltas = if julia {
+            series.coefficients[0] = one.clone();
+            probes.to_vec()
+        } else {
+            vec![zero.clone(); probes.len()]
+        };
+        let dc = |probe: &Complex<T>| if julia { zero.clone() } else { probe.clone() };
+
+        let end = std::cmp::min(max_iter as usize, orbit.len() - 1);
+        for n in 0..end {
+            let reference = Complex(T::from_f64(orbit[n].0), T::from_f64(orbit[n].1));
+            let twice = reference.clone() + reference;
+            let a = &series.coefficients;
+
+            // Squaring the series shifts products of pairs of terms up to higher powers of dc:
+            //   a_k <- 2 Z a_k + sum(a_i a_j, i + j = k - 1) (+ 1 for the dc term, except for Julia)
+            let coefficients: Vec<_> = (0..TERMS)
+                .map(|k| {
+                    let mut term = twice.clone() * a[k].clone();
+                    for i in 0..k {
+                        term = term + a[i].clone() * a[k - 1 - i].clone();
+                    }
+                    if k == 0 && !julia {
+                        term = term + one.clone();
+                    }
+                    term
+                })
+                .collect();
+            let next = Series {
+                iterations: n as u32 + 1,
+                coefficients,
+            };
+
+            // Iterate the probes exactly, and make sure the series still agrees with each of them
+            let reference = orbit[n + 1];
+            let mut valid = true;
+            for (dz, probe) in deltas.iter_mut().zip(probes) {
+                *dz = twice.clone() * dz.clone() + dz.clone() * dz.clone() + dc(probe);
+
+                let error = next.evaluate(probe) - dz.clone();
+                let z = reference + dz.to_f64();
+
+                // A probe that diverges (or gets near zero, where perturbation loses precision)
+                // can't be skipped past either.  This is written so that NaNs count as errors.
+                let accurate = error.abs_squared() <= dz.abs_squared() * tolerance.clone();
+                if !accurate
+                    || bailout.escaped(&z, radius)
+                    || z.abs_squared() < reference.abs_squared() * 1e-6
+                {
+                    valid = false;
+                }
+            }
+
+            if !valid {
+                break;
+            }
+            series = next;
+        }
+
+        series
+    }
+
+    // Evaluate the series for the given offset, giving the offset of z after the skipped iterations
+    pub fn evaluate(&self, offset: &Complex<T>) -> Complex<T> {
+        let zero = Complex(T::from_f64(0.0), T::from_f64(0.0));
+
+        // Horner's method, with an extra factor of the offset since there's no constant term
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(zero, |sum, a| sum * offset.clone() + a.clone())
+            * offset.clone()
+    }
+
+    // Evaluate the derivative of the series with respect to the offset, which is the derivative of z
+    // after the skipped iterations
+    pub fn derivative(&self, offset: &Complex<T>) -> Complex<T> {
+        let zero = Complex(T::from_f64(0.0), T::from_f64(0.0));
+
+        // Each coefficient a_k multiplies offset^(k + 1), so contributes (k + 1) a_k offset^k
+        self.coefficients
+            .iter()
+            .enumerate()
+            .rev()
+            .fold(zero, |sum, (k, a)| {
+                let scale = Complex(T::from_f64((k + 1) as f64), T::from_f64(0.0));
+                sum * offset.clone() + scale * a.clone()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{ComplexExp, FloatExp, Real};
+
+    fn complex(re: f64, im: f64) -> ComplexExp {
+        Complex(FloatExp::from(re), FloatExp::from(im))
+    }
+
+    #[test]
+    fn skips_iterations_at_a_deep_zoom() {
+        // Reference orbit at the center of a window of radius 1e-30 near the dendrite at the top of
+        // the set, worked out at full precision
+        let c = Complex(
+            Real::parse("-0.10109636384562", 160).unwrap(),
+            Real::parse("0.95628651080914", 160).unwrap(),
+        );
+        let mut z = Complex(Real::zero(160), Real::zero(160));
+        let mut orbit = vec![z.to_f64()];
+        while orbit.len() <= 1000 && !Bailout::Norm.escaped(orbit.last().unwrap(), 2.0) {
+            z = z.clone() * z + c.clone();
+            orbit.push(z.to_f64());
+        }
+
+        let radius = 1e-30;
+        let probes: Vec<ComplexExp> = (0..25)
+            .map(|p| complex((p % 5 - 2) as f64, (p / 5 - 2) as f64 * 0.5))
+            .map(|probe| probe * complex(radius, 0.0))
+            .collect();
+        let series = Series::new(&orbit, &probes, 1000, false, (Bailout::Norm, 2.0));
+        assert!(series.iterations > 0);
+
+        // Iterating the offset of a pixel between the probes one step at a time relative to the
+        // reference ends up where the series puts it
+        let dc = complex(0.3 * radius, -0.7 * radius);
+        let mut dz = complex(0.0, 0.0);
+        for reference in &orbit[..series.iterations as usize] {
+            let reference = complex(reference.0, reference.1);
+            dz = (reference + reference) * dz + dz * dz + dc;
+        }
+        let error = series.evaluate(&dc) - dz;
+        assert!(error.abs_squared() <= dz.abs_squared() * FloatExp::from(1e-16));
+    }
+}
@@ -0,0 +1,274 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::math::Complex;
+
+#[derive(Clone, PartialEq, Debug)]
+// Shape that orbits are measured against for orbit trap coloring.  Each pixel keeps the closest its
+// orbit came to the trap, and where it was at the time.
+pub enum Trap {
+    Point(Complex),
+    // Line through the point, at the angle (in degrees) from the real axis
+    Line(Complex, f64),
+    // Horizontal and vertical lines through the point
+    Cross(Complex),
+    // Circle around the point, with the radius
+    Circle(Complex, f64),
+    // Picture read from the path, centered on the point and scaled to the width
+    Image(TrapImage),
+}
+
+#[derive(Clone, Debug)]
+pub struct TrapImage {
+    path: String,
+    center: Complex,
+    width: f64,
+    pixels: Arc<image::RgbaImage>,
+}
+
+impl Trap {
+    // Parse a trap given by the user, where "none" means not to use one
+    pub fn parse_option(s: &str) -> Result<Option<Trap>, String> {
+        match s.trim() {
+            "none" => Ok(None),
+            s => s.parse().map(Some),
+        }
+    }
+
+    // Show a trap that may not be set, the same way parse_option takes it
+    pub fn describe(trap: Option<&Trap>) -> String {
+        match trap {
+            Some(trap) => trap.to_string(),
+            None => "none".into(),
+        }
+    }
+
+    // How far z is from the trap.  An image only counts as hit where its pixels aren't transparent,
+    // and anywhere else inside it is infinitely far away.
+    pub fn distance(&self, z: Complex) -> f64 {
+        match self {
+            Trap::Point(point) => (z - *point).abs(),
+            Trap::Line(point, angle) => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                let offset = z - *point;
+                (offset.0 * sin - offset.1 * cos).abs()
+            }
+            Trap::Cross(point) => (z.0 - point.0).abs().min((z.1 - point.1).abs()),
+            Trap::Circle(point, radius) => ((z - *point).abs() - radius).abs(),
+            Trap::Image(image) => match image.pixel(z) {
+                Some([.., 0]) => f64::INFINITY,
+                Some(_) => 0.0,
+                None => {
+                    let (half_width, half_height) = image.half_size();
+                    let dx = ((z.0 - image.center.0).abs() - half_width).max(0.0);
+                    let dy = ((z.1 - image.center.1).abs() - half_height).max(0.0);
+                    (dx * dx + dy * dy).sqrt()
+                }
+            },
+        }
+    }
+
+    // Color of the image at z, if the trap is an image and z is inside it, with each component
+    // between 0 and 1
+    pub fn color(&self, z: Complex) -> Option<(f32, f32, f32)> {
+        match self {
+            Trap::Image(image) => image
+                .pixel(z)
+                .map(|[r, g, b, _]| (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)),
+            _ => None,
+        }
+    }
+}
+
+impl TrapImage {
+    // Half of the width and height that the image covers in the complex plane
+    fn half_size(&self) -> (f64, f64) {
+        let (w, h) = self.pixels.dimensions();
+        (self.width / 2.0, self.width * h as f64 / w as f64 / 2.0)
+    }
+
+    // The image's pixel under z, if it's inside the image
+    fn pixel(&self, z: Complex) -> Option<[u8; 4]> {
+        let (w, h) = self.pixels.dimensions();
+        let (half_width, half_height) = self.half_size();
+        let scale = w as f64 / self.width;
+
+        // The top of the image is at the top, where the imaginary part is largest
+        let x = (z.0 - self.center.0 + half_width) * scale;
+        let y = (self.center.1 + half_height - z.1) * scale;
+        if x >= 0.0 && y >= 0.0 && x < w as f64 && y < h as f64 {
+            Some(self.pixels.get_pixel(x as u32, y as u32).data)
+        } else {
+            None
+        }
+    }
+}
+
+// Images are the same if they're read from the same path to the same place
+impl PartialEq for TrapImage {
+    fn eq(&self, other: &TrapImage) -> bool {
+        self.path == other.path && self.center == other.center && self.width == other.width
+    }
+}
+
+// Allow for parsing traps from user input, as the shape followed by its parameters in parentheses,
+// like `point(0,0)`, `line(0,0,45)`, `cross(0,0)`, `circle(0,0,1)` or `image(trap.png,0,0,2)`
+impl FromStr for Trap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (shape, params) = match (s.find('('), s.ends_with(')')) {
+            (Some(open), true) => (&s[..open], &s[open + 1..s.len() - 1]),
+            _ => return Err(format!("Invalid trap: {}.", s)),
+        };
+        let params: Vec<_> = params.split(',').map(str::trim).collect();
+
+        let number = |param: &str| match param.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => Err(format!("Invalid trap parameter: {}.", param)),
+        };
+        let count = |n: usize| {
+            if params.len() == n {
+                Ok(())
+            } else {
+                Err(format!("Incorrect syntax: {} has {} parameters.", shape, n))
+            }
+        };
+
+        match shape.trim() {
+            "point" => {
+                count(2)?;
+                Ok(Trap::Point(Complex(number(params[0])?, number(params[1])?)))
+            }
+            "line" => {
+                count(3)?;
+                let point = Complex(number(params[0])?, number(params[1])?);
+                Ok(Trap::Line(point, number(params[2])?))
+            }
+            "cross" => {
+                count(2)?;
+                Ok(Trap::Cross(Complex(number(params[0])?, number(params[1])?)))
+            }
+            "circle" => {
+                count(3)?;
+                let point = Complex(number(params[0])?, number(params[1])?);
+                match number(params[2])? {
+                    radius if radius > 0.0 => Ok(Trap::Circle(point, radius)),
+                    _ => Err(format!("Invalid trap radius: {}.", params[2])),
+                }
+            }
+            "image" => {
+                // Commas would split the path up, and whitespace would split a GUI command up
+                let path = params[..params.len().saturating_sub(3)].join(",");
+                if path.contains(|c: char| c == ',' || c.is_whitespace()) {
+                    return Err(format!(
+                        "Image path can't contain commas or whitespace: {}.",
+                        path
+                    ));
+                }
+                count(4)?;
+                let width = match number(params[3])? {
+                    width if width > 0.0 => width,
+                    _ => return Err(format!("Invalid trap width: {}.", params[3])),
+                };
+                let pixels = match image::open(params[0]) {
+                    Ok(image) => image.to_rgba(),
+                    Err(e) => return Err(format!("Couldn't read image {}: {}.", params[0], e)),
+                };
+                if pixels.width() == 0 || pixels.height() == 0 {
+                    return Err(format!("Image {} is empty.", params[0]));
+                }
+
+                Ok(Trap::Image(TrapImage {
+                    path: params[0].to_string(),
+                    center: Complex(number(params[1])?, number(params[2])?),
+                    width,
+                    pixels: Arc::new(pixels),
+                }))
+            }
+            _ => Err(format!("No such trap: {}.", shape.trim())),
+        }
+    }
+}
+
+// Traps are printed without any whitespace, so that they fit in a single part of a GUI command
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Trap::Point(point) => write!(f, "point({},{})", point.0, point.1),
+            Trap::Line(point, angle) => write!(f, "line({},{},{})", point.0, point.1, angle),
+            Trap::Cross(point) => write!(f, "cross({},{})", point.0, point.1),
+            Trap::Circle(point, radius) => {
+                write!(f, "circle({},{},{})", point.0, point.1, radius)
+            }
+            Trap::Image(image) => write!(
+                f,
+                "image({},{},{},{})",
+                image.path, image.center.0, image.center.1, image.width
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        for s in &[
+            "none",
+            "point(0.5,-1)",
+            "line(0,0,45)",
+            "cross(-0.75,0.1)",
+            "circle(0,0,1.5)",
+        ] {
+            let trap = Trap::parse_option(s).unwrap();
+            assert_eq!(Trap::describe(trap.as_ref()), *s);
+        }
+        assert_eq!(
+            " circle( 1 , 2 , 3 ) ".parse(),
+            Ok(Trap::Circle(Complex(1.0, 2.0), 3.0))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        for s in &[
+            "point",
+            "point(1)",
+            "point(0,inf)",
+            "line(0,0)",
+            "circle(0,0,-1)",
+            "square(0,0)",
+            "image(missing.png,0,0,1)",
+        ] {
+            assert!(s.parse::<Trap>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn images() {
+        let path = std::env::temp_dir().join("trap_test.png");
+        let mut pixels = image::RgbaImage::new(2, 1);
+        pixels.put_pixel(0, 0, image::Rgba([255, 0, 0, 255]));
+        pixels.save(&path).unwrap();
+
+        // The image covers -1..1 by -0.5..0.5, with a red left half and a transparent right half
+        let s = format!("image({},0,0,2)", path.display());
+        let trap: Trap = s.parse().unwrap();
+        assert_eq!(trap.to_string(), s);
+        assert_eq!(trap.color(Complex(-0.5, 0.0)), Some((1.0, 0.0, 0.0)));
+        assert_eq!(trap.distance(Complex(-0.5, 0.0)), 0.0);
+        assert_eq!(trap.distance(Complex(0.5, 0.0)), f64::INFINITY);
+        assert_eq!(trap.distance(Complex(-4.0, 4.5)), 5.0);
+
+        // Paths with commas or whitespace couldn't be printed back in one piece
+        for s in &["image(a,b.png,0,0,2)", "image(my trap.png,0,0,2)"] {
+            let error = s.parse::<Trap>().unwrap_err();
+            assert!(error.contains("commas or whitespace"), "{}", error);
+        }
+    }
+}
@@ -1,12 +1,15 @@
 use std::path::Path;
 use std::io::{self, prelude::*};
 
+use crate::compute::Backend;
+
 // Holds information about the program configuration for this execution,
-// including whether or not to run in GUI mode and what port to use to
-// communicate to the GUI if applicable.
+// including whether or not to run in GUI mode, what port to use to
+// communicate to the GUI if applicable, and which backend to compute with.
 pub struct Config {
     pub gui: bool,
     pub port: Option<u16>,
+    pub backend: Backend,
 }
 
 // Parse the command line arguments into a Config instance
@@ -15,6 +18,7 @@ pub fn parse_args(args: Vec<String>) -> Config {
     let mut config = Config {
         gui: false,
         port: None,
+        backend: Backend::Auto,
     };
 
     // What we're expecting for the next iteration (-1 if anything)
@@ -32,8 +36,11 @@ pub fn parse_args(args: Vec<String>) -> Config {
             } else if arg == "-p" || arg == "--port" {
                 // Specifying the port
                 expecting = 1;
+            } else if arg == "-b" || arg == "--backend" {
+                // Specifying the backend
+                expecting = 2;
             } else {
-                panic!(format!("Unknown option: {}", arg));
+                panic!("Unknown option: {}", arg);
             }
         } else {
             match expecting {
@@ -59,6 +66,16 @@ pub fn parse_args(args: Vec<String>) -> Config {
                     // Nothing else to expect
                     expecting = -1;
                 }
+                2 => {
+                    // Expecting to find a backend
+                    config.backend = match arg.parse::<Backend>() {
+                        Ok(backend) => backend,
+                        Err(e) => panic!("{} Must be one of cpu, cuda, perturbation or auto.", e),
+                    };
+
+                    // Nothing else to expect
+                    expecting = -1;
+                }
                 _ => unreachable!(),
             }
         }
@@ -67,6 +84,7 @@ pub fn parse_args(args: Vec<String>) -> Config {
     // If expecting something, there's an error
     match expecting {
         1 => panic!("Must specify port with --port (-p) flag."),
+        2 => panic!("Must specify backend with --backend (-b) flag."),
         _ => (),
     }
 